@@ -0,0 +1,172 @@
+use super::{ConfigReader, CulperConfig};
+use failure::{Error, ResultExt};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use toml::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigChange {
+    Added(String),
+    Removed(String),
+    Changed(String),
+}
+
+/// Dotted paths of what differs between two configs. Targets and users are
+/// addressed by id and fingerprint, e.g. `targets.web.host`. Repeats of an id
+/// are numbered from the second one on, e.g. `targets.web#2`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub changes: Vec<ConfigChange>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl CulperConfig {
+    /// What changes going from `self` to `other`. Reordering targets or users
+    /// doesn't count as a change.
+    pub fn diff(&self, other: &CulperConfig) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+        match (Value::try_from(self), Value::try_from(other)) {
+            (Ok(before), Ok(after)) => diff_values("", &before, &after, &mut diff.changes),
+            // Both are plain data, serializing them can't fail.
+            _ => unreachable!("config could not be converted to TOML"),
+        }
+        diff
+    }
+}
+
+impl ConfigReader {
+    /// What writing the loaded config would change in the file as it is on
+    /// disk now, including edits someone else made since it was read.
+    pub fn diff_against_disk(&self) -> Result<ConfigDiff, Error> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return Err(format_err!("Config is not set.")),
+        };
+        let raw_toml = fs::read_to_string(self.require_path()?)
+            .context("Could not read configuration file")?;
+        Ok(self.read_string_to_config(&raw_toml)?.diff(config))
+    }
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, changes: &mut Vec<ConfigChange>) {
+    match (before, after) {
+        (Value::Table(before), Value::Table(after)) => {
+            let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+            for key in keys {
+                let path = join(path, key);
+                match (before.get(key), after.get(key)) {
+                    (Some(before), Some(after)) => diff_values(&path, before, after, changes),
+                    (Some(_), None) => changes.push(ConfigChange::Removed(path)),
+                    (None, Some(_)) => changes.push(ConfigChange::Added(path)),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(before_items), Value::Array(after_items)) => {
+            match (keyed(before_items), keyed(after_items)) {
+                (Some(before), Some(after)) => {
+                    diff_values(path, &Value::Table(before), &Value::Table(after), changes)
+                }
+                _ if before != after => changes.push(ConfigChange::Changed(path.to_owned())),
+                _ => {}
+            }
+        }
+        _ if before != after => changes.push(ConfigChange::Changed(path.to_owned())),
+        _ => {}
+    }
+}
+
+// An array of tables that all carry an `id` or `fingerprint`, as a table by that
+// key. Duplicates get their own `#2`, `#3`, ... keys instead of collapsing.
+fn keyed(items: &[Value]) -> Option<toml::value::Table> {
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    items
+        .iter()
+        .map(|item| {
+            let table = item.as_table()?;
+            let identity = table.get("id").or_else(|| table.get("fingerprint"))?;
+            let identity = identity.as_str()?;
+            let count = seen.entry(identity).or_insert(0);
+            *count += 1;
+            let key = match *count {
+                1 => identity.to_owned(),
+                n => format!("{}#{}", identity, n),
+            };
+            Some((key, item.clone()))
+        })
+        .collect()
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::{remove_temp_config, test_config};
+    use crate::config::TargetConfig;
+
+    #[test]
+    fn reports_memory_and_disk_changes() {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!("culper-lib-diff-{}.toml", ::std::process::id()));
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(test_config());
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.write().unwrap();
+        assert!(config_reader.diff_against_disk().unwrap().is_empty());
+
+        config_reader.add_target("db.test.de", "db").unwrap();
+        let on_disk = fs::read_to_string(&path).unwrap();
+        fs::write(&path, on_disk.replace("test@test.de", "someone@test.de")).unwrap();
+
+        let diff = config_reader.diff_against_disk().unwrap();
+        remove_temp_config(&path);
+        assert_eq!(
+            diff.changes,
+            vec![
+                ConfigChange::Changed("identity.name".to_owned()),
+                ConfigChange::Added("targets.db".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicated_ids() {
+        let target = TargetConfig {
+            id: "web".to_owned(),
+            host: "www.test.de".to_owned(),
+            port: None,
+            format: None,
+            description: None,
+            tags: None,
+        };
+        let before = CulperConfig {
+            targets: Some(vec![target.clone()]),
+            ..test_config()
+        };
+        let after = CulperConfig {
+            targets: Some(vec![target.clone(), target]),
+            ..test_config()
+        };
+
+        assert_eq!(
+            before.diff(&after).changes,
+            vec![ConfigChange::Added("targets.web#2".to_owned())]
+        );
+        assert_eq!(
+            after.diff(&before).changes,
+            vec![ConfigChange::Removed("targets.web#2".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use super::RENAMED_KEYS;
+use failure::Error;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
+
+/// Applies a freshly rendered config onto the existing file contents, so
+/// comments and layout around unchanged keys survive a `write`. Entries in
+/// arrays of tables are matched up by their `id` or `fingerprint`.
+pub(super) fn merge_into(existing: &str, rendered: &str) -> Result<String, Error> {
+    let mut document: DocumentMut = existing.parse()?;
+    let updated: DocumentMut = rendered.parse()?;
+    for (old_key, new_key) in RENAMED_KEYS {
+        if updated.contains_key(new_key) && !document.contains_key(new_key) {
+            rename_key(document.as_table_mut(), old_key, new_key);
+        }
+    }
+    merge_table(document.as_table_mut(), updated.as_table());
+    Ok(document.to_string())
+}
+
+// Tables sharing a position are written in the order their keys were inserted,
+// so the renamed key has to go back in its old slot rather than at the end.
+fn rename_key(table: &mut Table, old_key: &str, new_key: &str) {
+    if !table.contains_key(old_key) {
+        return;
+    }
+    let entries: Vec<(String, Item)> = table
+        .iter()
+        .map(|(key, item)| (key.to_owned(), item.clone()))
+        .collect();
+    table.clear();
+    for (key, item) in entries {
+        let key = if key == old_key { new_key } else { &key };
+        table.insert(key, item);
+    }
+}
+
+fn merge_table(old: &mut Table, new: &Table) {
+    let stale: Vec<String> = old
+        .iter()
+        .map(|(key, _)| key.to_owned())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in stale {
+        old.remove(&key);
+    }
+
+    for (key, item) in new.iter() {
+        match (old.get_mut(key), item) {
+            (Some(Item::Table(old_table)), Item::Table(new_table)) => {
+                merge_table(old_table, new_table)
+            }
+            (Some(Item::ArrayOfTables(old_array)), Item::ArrayOfTables(new_array)) => {
+                merge_array(old_array, new_array)
+            }
+            (Some(Item::Value(old_value)), Item::Value(new_value)) => {
+                if !same_value(old_value, new_value) {
+                    let decor = old_value.decor().clone();
+                    *old_value = new_value.clone();
+                    *old_value.decor_mut() = decor;
+                }
+            }
+            (Some(old_item), _) => *old_item = item.clone(),
+            (None, _) => {
+                old.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+fn merge_array(old: &mut ArrayOfTables, new: &ArrayOfTables) {
+    let mut previous: Vec<Option<Table>> = old.iter().cloned().map(Some).collect();
+    let merged: Vec<Table> = new
+        .iter()
+        .map(|new_table| {
+            let matching = previous
+                .iter_mut()
+                .find(|table| match table {
+                    Some(table) => {
+                        identity(table).is_some() && identity(table) == identity(new_table)
+                    }
+                    None => false,
+                })
+                .and_then(Option::take);
+            match matching {
+                Some(mut table) => {
+                    merge_table(&mut table, new_table);
+                    table
+                }
+                None => new_table.clone(),
+            }
+        })
+        .collect();
+
+    old.clear();
+    for table in merged {
+        old.push(table);
+    }
+}
+
+fn identity(table: &Table) -> Option<&str> {
+    table
+        .get("id")
+        .or_else(|| table.get("fingerprint"))
+        .and_then(Item::as_str)
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        (Value::InlineTable(a), Value::InlineTable(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same_value(a, b)))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_comments_on_untouched_keys() {
+        let existing =
+            "# who I am\n[me]\nname = 'test@test.de' # keep me\nfingerprint = \"12345678\"\n";
+        let rendered = "[me]\nname = \"test@test.de\"\nfingerprint = \"ABCDEF01\"\n";
+
+        assert_eq!(
+            merge_into(existing, rendered).unwrap(),
+            "# who I am\n[me]\nname = 'test@test.de' # keep me\nfingerprint = \"ABCDEF01\"\n"
+        );
+    }
+
+    #[test]
+    fn moves_renamed_keys_in_place() {
+        let existing = "# who I am\n[me]\nname = 'test@test.de'\n\n[[targets]]\nid = \"web\"\n";
+        let rendered = "[identity]\nname = \"test@test.de\"\n\n[[targets]]\nid = \"web\"\n";
+
+        assert_eq!(
+            merge_into(existing, rendered).unwrap(),
+            "# who I am\n[identity]\nname = 'test@test.de'\n\n[[targets]]\nid = \"web\"\n"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use super::{CulperConfig, UserConfig};
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::value::Datetime;
+
+impl CulperConfig {
+    /// Users whose `expires_at` is at or before `now`, starting with `me`, then
+    /// owners and admins. Users without a usable expiry date never expire.
+    pub fn expired_users(&self, now: SystemTime) -> Vec<&UserConfig> {
+        let now = match now.duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as i64,
+            Err(before_epoch) => -(before_epoch.duration().as_secs() as i64),
+        };
+        ::std::iter::once(&self.me)
+            .chain(self.owners.iter().flatten())
+            .chain(self.admins.iter().flatten())
+            .filter(
+                |user| match user.expires_at.as_ref().and_then(unix_seconds) {
+                    Some(expires_at) => expires_at <= now,
+                    None => false,
+                },
+            )
+            .collect()
+    }
+}
+
+/// Seconds since the epoch. Dates without a time mean midnight and times without
+/// an offset are taken as UTC; a bare time of day has no date and yields `None`.
+fn unix_seconds(datetime: &Datetime) -> Option<i64> {
+    let text = datetime.to_string();
+    let (date, time) = match text.find(['T', 't', ' ']) {
+        Some(split) => (&text[..split], Some(&text[split + 1..])),
+        None => (text.as_str(), None),
+    };
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+    let mut seconds = days_from_civil(year, month, day) * 86_400;
+
+    if let Some(time) = time {
+        let (clock, offset) = if time.ends_with('Z') || time.ends_with('z') {
+            (&time[..time.len() - 1], 0)
+        } else {
+            match time.rfind(['+', '-']) {
+                Some(split) => (&time[..split], offset_seconds(&time[split..])?),
+                None => (time, 0),
+            }
+        };
+        let mut clock_parts = clock.split('.').next()?.splitn(3, ':');
+        let hours: i64 = clock_parts.next()?.parse().ok()?;
+        let minutes: i64 = clock_parts.next()?.parse().ok()?;
+        let secs: i64 = clock_parts.next()?.parse().ok()?;
+        seconds += hours * 3_600 + minutes * 60 + secs - offset;
+    }
+    Some(seconds)
+}
+
+fn offset_seconds(offset: &str) -> Option<i64> {
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let mut parts = offset[1..].splitn(2, ':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next()?.parse().ok()?;
+    Some(sign * (hours * 3_600 + minutes * 60))
+}
+
+// Days between 1970-01-01 and the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigReader;
+    use std::time::Duration;
+
+    fn datetime(value: &str) -> Datetime {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn converts_toml_datetimes() {
+        assert_eq!(unix_seconds(&datetime("1970-01-01")), Some(0));
+        assert_eq!(
+            unix_seconds(&datetime("1970-01-02T00:00:01Z")),
+            Some(86_401)
+        );
+        assert_eq!(
+            unix_seconds(&datetime("1970-01-01T02:00:00+02:00")),
+            Some(0)
+        );
+        assert_eq!(
+            unix_seconds(&datetime("2024-03-01T12:30:00.250")),
+            Some(1_709_296_200)
+        );
+        assert_eq!(unix_seconds(&datetime("12:30:00")), None);
+    }
+
+    #[test]
+    fn lists_expired_users() {
+        let raw_toml = r#"
+            [me]
+            name = "me@test.de"
+            fingerprint = "12345678"
+            added_at = 2019-01-01T00:00:00+02:00
+            expires_at = 2030-06-01T12:00:00Z
+
+            [[owners]]
+            name = "expired@test.de"
+            fingerprint = "ABCDEF01"
+            expires_at = 2024-12-31
+
+            [[owners]]
+            name = "forever@test.de"
+            fingerprint = "ABCDEF02"
+        "#;
+        let config = ConfigReader::from_reader(raw_toml.as_bytes())
+            .unwrap()
+            .config
+            .unwrap();
+        assert_eq!(
+            config.me.added_at.as_ref().map(ToString::to_string),
+            Some("2019-01-01T00:00:00+02:00".to_owned())
+        );
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_735_689_600); // 2025-01-01
+        let expired: Vec<&str> = config
+            .expired_users(now)
+            .into_iter()
+            .map(|user| user.name.as_str())
+            .collect();
+        assert_eq!(expired, vec!["expired@test.de"]);
+    }
+}
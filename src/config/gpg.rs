@@ -0,0 +1,101 @@
+use failure::{Error, ResultExt};
+use std::process::Command;
+
+/// The fingerprint of the only secret key in the local GPG keyring, to prefill
+/// `me` without copying it by hand. Several secret keys are an error, as
+/// there is no telling which one is meant.
+pub fn detect_default_fingerprint() -> Result<String, Error> {
+    let output = Command::new("gpg")
+        .args(["--batch", "--with-colons", "--list-secret-keys"])
+        .output()
+        .context("Could not run gpg")?;
+    if !output.status.success() {
+        return Err(format_err!(
+            "gpg failed to list secret keys: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    single_fingerprint(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn single_fingerprint(colons: &str) -> Result<String, Error> {
+    let fingerprints = secret_key_fingerprints(colons);
+    match fingerprints.as_slice() {
+        [fingerprint] => Ok(fingerprint.clone()),
+        [] => Err(format_err!("No secret key found in the GPG keyring.")),
+        _ => Err(format_err!(
+            "Found {} secret keys, set identity.fingerprint explicitly: {}",
+            fingerprints.len(),
+            fingerprints.join(", ")
+        )),
+    }
+}
+
+// In `--with-colons` output, the first `fpr` record after a `sec` record holds
+// the primary key's fingerprint; later ones belong to its subkeys.
+fn secret_key_fingerprints(colons: &str) -> Vec<String> {
+    let mut fingerprints = Vec::new();
+    let mut in_primary = false;
+    for line in colons.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.first() {
+            Some(&"sec") => in_primary = true,
+            Some(&"fpr") if in_primary => {
+                if let Some(fingerprint) = fields.get(9).filter(|fpr| !fpr.is_empty()) {
+                    fingerprints.push((*fingerprint).to_owned());
+                }
+                in_primary = false;
+            }
+            Some(&"ssb") => in_primary = false,
+            _ => {}
+        }
+    }
+    fingerprints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_KEY: &str = "sec:u:255:22:1E2BC9BE7F3AD9A1:1600000000:::u:::scESC:::+:::ed25519:::0:
+fpr:::::::::0E2DF37CE37B47F1B8C4EC8C1E2BC9BE7F3AD9A1:
+grp:::::::::1A2B3C:
+uid:u::::1600000000::HASH::Test User <test@test.de>::::::::::0:
+ssb:u:255:18:9F00AA11BB22CC33:1600000000::::::e:::+:::cv25519::
+fpr:::::::::51C1B3B2E7F5D3A99A1F6E259F00AA11BB22CC33:
+";
+
+    #[test]
+    fn reads_primary_fingerprint() {
+        assert_eq!(
+            single_fingerprint(ONE_KEY).unwrap(),
+            "0E2DF37CE37B47F1B8C4EC8C1E2BC9BE7F3AD9A1"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_ambiguous_keys() {
+        assert_eq!(
+            single_fingerprint("").err().unwrap().to_string(),
+            "No secret key found in the GPG keyring."
+        );
+
+        let two_keys = format!(
+            "{}sec:u:255:22:AAAA:1600000000:::u:::scESC:::+:::ed25519:::0:\nfpr:::::::::AAAA:\n",
+            ONE_KEY
+        );
+        assert_eq!(
+            single_fingerprint(&two_keys).err().unwrap().to_string(),
+            "Found 2 secret keys, set identity.fingerprint explicitly: \
+             0E2DF37CE37B47F1B8C4EC8C1E2BC9BE7F3AD9A1, AAAA"
+        );
+    }
+
+    // Needs a keyring with a single secret key, e.g. via GNUPGHOME.
+    #[test]
+    fn detects_fingerprint_from_test_keyring() {
+        if let Ok(expected) = ::std::env::var("CULPER_TEST_GPG_FINGERPRINT") {
+            assert_eq!(detect_default_fingerprint().unwrap(), expected);
+        }
+    }
+}
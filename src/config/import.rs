@@ -0,0 +1,119 @@
+use super::ConfigReader;
+use failure::Error;
+use std::collections::BTreeSet;
+
+/// `(host, id)` pairs from the `Host` blocks of an SSH config, in the shape
+/// `ConfigReader::add_targets` takes. Every alias of a block becomes an id
+/// pointing at its `HostName`, or at the alias itself without one. Wildcard
+/// patterns and `Match` blocks are skipped.
+pub fn parse_ssh_config(text: &str) -> Vec<(String, String)> {
+    fn flush(
+        aliases: &mut Vec<String>,
+        host_name: &mut Option<String>,
+        into: &mut Vec<(String, String)>,
+    ) {
+        for alias in aliases.drain(..) {
+            let host = host_name.clone().unwrap_or_else(|| alias.clone());
+            into.push((host, alias));
+        }
+        *host_name = None;
+    }
+
+    let mut pairs = Vec::new();
+    let mut aliases: Vec<String> = Vec::new();
+    let mut host_name = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (keyword, arguments) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+            Some(split) => (
+                &line[..split],
+                line[split + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == '='),
+            ),
+            None => (line, ""),
+        };
+        if keyword.eq_ignore_ascii_case("Host") || keyword.eq_ignore_ascii_case("Match") {
+            flush(&mut aliases, &mut host_name, &mut pairs);
+            if keyword.eq_ignore_ascii_case("Host") {
+                aliases = arguments
+                    .split_whitespace()
+                    .filter(|alias| !alias.contains(['*', '?', '!']))
+                    .map(str::to_owned)
+                    .collect();
+            }
+        } else if keyword.eq_ignore_ascii_case("HostName") && !aliases.is_empty() {
+            host_name = arguments.split_whitespace().next().map(str::to_owned);
+        }
+    }
+    flush(&mut aliases, &mut host_name, &mut pairs);
+    pairs
+}
+
+impl ConfigReader {
+    /// Adds `(host, id)` pairs as targets, skipping ids that already exist or
+    /// repeat. Returns how many were added.
+    pub fn import_hosts(&mut self, hosts: &[(String, String)]) -> Result<usize, Error> {
+        let mut seen: BTreeSet<String> = self.target_ids().into_iter().collect();
+        let new_hosts: Vec<(String, String)> = hosts
+            .iter()
+            .filter(|(_, id)| seen.insert(id.clone()))
+            .cloned()
+            .collect();
+        let count = new_hosts.len();
+        self.add_targets(new_hosts)?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::test_config;
+
+    const SSH_CONFIG: &str = "
+# shared defaults
+Host *
+    User deploy
+
+Host web www
+    HostName www.test.de
+    Port 2222
+
+Host db
+    HostName=db.test.de
+
+Host bastion
+";
+
+    #[test]
+    fn parses_host_blocks() {
+        assert_eq!(
+            parse_ssh_config(SSH_CONFIG),
+            vec![
+                ("www.test.de".to_owned(), "web".to_owned()),
+                ("www.test.de".to_owned(), "www".to_owned()),
+                ("db.test.de".to_owned(), "db".to_owned()),
+                ("bastion".to_owned(), "bastion".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn imports_hosts_as_targets() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        config_reader.add_target("old-db.test.de", "db").unwrap();
+
+        let imported = config_reader
+            .import_hosts(&parse_ssh_config(SSH_CONFIG))
+            .unwrap();
+        assert_eq!(imported, 3);
+        assert_eq!(
+            config_reader.target_ids(),
+            vec!["db", "web", "www", "bastion"]
+        );
+        assert_eq!(config_reader.targets()[0].host, "old-db.test.de");
+    }
+}
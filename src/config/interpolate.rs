@@ -0,0 +1,80 @@
+use failure::Error;
+use std::env;
+use toml::Value;
+
+pub fn interpolate_env(value: &mut Value) -> Result<(), Error> {
+    match value {
+        Value::String(string) => *string = interpolate_str(string)?,
+        Value::Array(values) => {
+            for value in values {
+                interpolate_env(value)?;
+            }
+        }
+        Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                interpolate_env(value)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str(input: &str) -> Result<String, Error> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format_err!("Unterminated variable reference in {:?}", input))?;
+        let reference = &rest[start + 2..start + end];
+        let (name, default) = match reference.find(":-") {
+            Some(split) => (&reference[..split], Some(&reference[split + 2..])),
+            None => (reference, None),
+        };
+        match (env::var(name), default) {
+            (Ok(value), _) => output.push_str(&value),
+            (Err(_), Some(default)) => output.push_str(default),
+            (Err(_), None) => return Err(format_err!("Undefined environment variable {}", name)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_defined_variables() {
+        env::set_var("CULPER_TEST_INTERPOLATE_HOST", "db.test.de");
+        assert_eq!(
+            interpolate_str("https://${CULPER_TEST_INTERPOLATE_HOST}/").unwrap(),
+            "https://db.test.de/"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_defaults() {
+        env::remove_var("CULPER_TEST_INTERPOLATE_UNSET");
+        assert_eq!(
+            interpolate_str("${CULPER_TEST_INTERPOLATE_UNSET:-localhost}").unwrap(),
+            "localhost"
+        );
+    }
+
+    #[test]
+    fn errors_on_undefined_variables() {
+        env::remove_var("CULPER_TEST_INTERPOLATE_MISSING");
+        let error = interpolate_str("${CULPER_TEST_INTERPOLATE_MISSING}")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Undefined environment variable CULPER_TEST_INTERPOLATE_MISSING"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use super::ConfigReader;
+use failure::{Error, ResultExt};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Holds the advisory lock on a config until dropped, see `ConfigReader::lock`.
+#[derive(Debug)]
+pub struct ConfigLockGuard {
+    file: File,
+    held: Arc<AtomicBool>,
+}
+
+impl Drop for ConfigLockGuard {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+        self.held.store(false, Ordering::SeqCst);
+    }
+}
+
+impl ConfigReader {
+    /// Takes an exclusive advisory lock on `<config>.lock`, blocking until other
+    /// processes let go, and keeps it until the guard is dropped. Writes through
+    /// this reader in the meantime don't lock again.
+    ///
+    /// `<config>.lock` stays on disk after the guard is dropped. Removing it
+    /// would let a process still waiting on the old file lock it while another
+    /// locks a new one, so both would write.
+    pub fn lock(&self) -> Result<ConfigLockGuard, Error> {
+        let path = self.require_path()?;
+        if self.lock_held.swap(true, Ordering::SeqCst) {
+            return Err(format_err!("This reader already holds the config lock."));
+        }
+        match lock_file(path) {
+            Ok(file) => Ok(ConfigLockGuard {
+                file,
+                held: Arc::clone(&self.lock_held),
+            }),
+            Err(error) => {
+                self.lock_held.store(false, Ordering::SeqCst);
+                Err(error)
+            }
+        }
+    }
+
+    /// A fresh lock for a single write, or none when a guard is already held.
+    pub(super) fn lock_for_write(&self) -> Result<Option<ConfigLockGuard>, Error> {
+        if self.lock_held.load(Ordering::SeqCst) {
+            Ok(None)
+        } else {
+            self.lock().map(Some)
+        }
+    }
+}
+
+fn lock_file(path: &Path) -> Result<File, Error> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    if let Some(parent) = path.parent() {
+        ::std::fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .context(format!("Could not open lock file {:?}", lock_path))?;
+    file.lock().context("Could not lock the config")?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::test_config;
+    use std::fs;
+
+    #[test]
+    fn writes_under_a_held_guard() {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!("culper-lib-lock-{}.toml", ::std::process::id()));
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(test_config());
+
+        let guard = config_reader.lock().unwrap();
+        assert!(config_reader.lock().is_err());
+        config_reader.write().unwrap();
+        config_reader.write_atomic_with_perms().unwrap();
+        drop(guard);
+
+        // Dropping the guard released it, so taking it again works.
+        let guard = config_reader.lock().unwrap();
+        drop(guard);
+        config_reader.write().unwrap();
+
+        fs::remove_file(&path).unwrap();
+        let mut lock_path = path.into_os_string();
+        lock_path.push(".lock");
+        fs::remove_file(lock_path).unwrap();
+    }
+
+    #[test]
+    fn clones_do_not_share_a_held_guard() {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!(
+            "culper-lib-lock-clone-{}.toml",
+            ::std::process::id()
+        ));
+        let config_reader = ConfigReader::new(path.to_str());
+
+        let guard = config_reader.lock().unwrap();
+        let clone = config_reader.clone();
+        assert!(config_reader.lock_for_write().unwrap().is_none());
+        assert!(!clone.lock_held.load(Ordering::SeqCst));
+        drop(guard);
+
+        let mut lock_path = path.into_os_string();
+        lock_path.push(".lock");
+        fs::remove_file(lock_path).unwrap();
+    }
+}
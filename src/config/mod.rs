@@ -1,123 +1,953 @@
+use crate::vault::EncryptionFormat;
+
+mod diff;
+mod edit;
+mod expiry;
+#[cfg(feature = "gpg")]
+mod gpg;
+mod import;
+mod interpolate;
+mod lock;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "schema")]
+mod schema;
+mod secrets;
+mod stats;
+mod validate;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use self::diff::{ConfigChange, ConfigDiff};
+#[cfg(feature = "gpg")]
+pub use self::gpg::detect_default_fingerprint;
+pub use self::import::parse_ssh_config;
+pub use self::lock::ConfigLockGuard;
+#[cfg(feature = "schema")]
+pub use self::schema::json_schema;
+pub use self::stats::ConfigStats;
+pub use self::validate::{Severity, ValidationIssue};
+#[cfg(feature = "watch")]
+pub use self::watch::WatchHandle;
 use dirs;
-use failure::{Context, Error, Fail, ResultExt};
-use std::fs::File;
+use failure::{Error, ResultExt};
+use glob::{MatchOptions, Pattern};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs::OpenOptions;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::SystemTime;
 use toml;
+use toml::value::Datetime;
+
+fn merge_list<T, K, F>(base: &mut Option<Vec<T>>, overlay: Option<Vec<T>>, key: F)
+where
+    K: PartialEq,
+    F: Fn(&T) -> K,
+{
+    let overlay = match overlay {
+        Some(overlay) => overlay,
+        None => return,
+    };
+    let existing = base.get_or_insert_with(Vec::new);
+    for item in overlay {
+        match existing.iter_mut().find(|known| key(known) == key(&item)) {
+            Some(known) => *known = item,
+            None => existing.push(item),
+        }
+    }
+}
+
+/// Environment variable naming the config file, see `ConfigReader::from_env_or_default`.
+pub const CONFIG_ENV_VAR: &str = "CULPER_CONFIG";
+
+/// Keys that were renamed, as `(old, new)`. Renamed fields keep reading the
+/// old key through `#[serde(alias)]` and are written under the new one; listing
+/// them here makes `write` move the old key in existing files instead of
+/// leaving it behind.
+pub(crate) const RENAMED_KEYS: &[(&str, &str)] = &[("me", "identity")];
+
+/// What a user is listed as. Admins rank above owners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    Owner,
+    Admin,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct CulperConfig {
+    /// Sidecar file holding sealed values by name, relative to the config file.
+    pub secrets_file: Option<String>,
     pub targets: Option<Vec<TargetConfig>>,
     pub owners: Option<Vec<UserConfig>>,
     pub admins: Option<Vec<UserConfig>>,
+    #[serde(rename = "identity", alias = "me")]
     pub me: UserConfig,
+    pub secrets: Option<BTreeMap<String, String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl CulperConfig {
+    pub fn all_fingerprints(&self) -> BTreeSet<String> {
+        let mut fingerprints = BTreeSet::new();
+        fingerprints.insert(normalize_fingerprint(&self.me.fingerprint));
+        for users in [&self.owners, &self.admins].iter().copied().flatten() {
+            for user in users {
+                fingerprints.insert(normalize_fingerprint(&user.fingerprint));
+            }
+        }
+        fingerprints
+    }
+
+    /// Every admin and owner with their role. Users listed as both come up once,
+    /// as `Admin`.
+    pub fn users_with_roles(&self) -> impl Iterator<Item = (&UserConfig, Role)> {
+        let admin_fingerprints: BTreeSet<String> = self
+            .admins
+            .iter()
+            .flatten()
+            .map(|user| normalize_fingerprint(&user.fingerprint))
+            .collect();
+        let admins = self.admins.iter().flatten().map(|user| (user, Role::Admin));
+        let owners = self
+            .owners
+            .iter()
+            .flatten()
+            .filter(move |user| {
+                !admin_fingerprints.contains(&normalize_fingerprint(&user.fingerprint))
+            })
+            .map(|user| (user, Role::Owner));
+        admins.chain(owners)
+    }
+
+    /// Every format this config needs a handler for: the default format, plus
+    /// whatever the targets ask for.
+    pub fn required_formats(&self) -> BTreeSet<EncryptionFormat> {
+        let mut formats = BTreeSet::new();
+        formats.insert(EncryptionFormat::default());
+        for target in self.targets.iter().flatten() {
+            if let Some(format) = &target.format {
+                if let Ok(format) = EncryptionFormat::from_str(format) {
+                    formats.insert(format);
+                }
+            }
+        }
+        formats
+    }
+
+    /// The encryption format preferred by the target, if it names a known one.
+    pub fn target_format(&self, id: &str) -> Option<EncryptionFormat> {
+        let target = self
+            .targets
+            .as_ref()?
+            .iter()
+            .find(|target| target.id == id)?;
+        EncryptionFormat::from_str(target.format.as_ref()?).ok()
+    }
+
+    /// Replaces `old_fp` with `new_fp` in `me`, owners and admins, comparing
+    /// normalized fingerprints. Returns how many entries changed.
+    pub fn rotate_fingerprint(&mut self, old_fp: &str, new_fp: &str) -> Result<usize, Error> {
+        let new_fp = normalize_fingerprint(new_fp);
+        if new_fp.is_empty() || !new_fp.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format_err!("Invalid fingerprint: {}", new_fp));
+        }
+        let old_fp = normalize_fingerprint(old_fp);
+
+        let mut count = 0;
+        let users = ::std::iter::once(&mut self.me)
+            .chain(self.owners.iter_mut().flatten())
+            .chain(self.admins.iter_mut().flatten());
+        for user in users {
+            if normalize_fingerprint(&user.fingerprint) == old_fp {
+                user.fingerprint = new_fp.clone();
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Layers `overlay` on top: its `me` wins, targets are unioned by id, users by
+    /// fingerprint and secrets by name, with the overlay's entry replacing ours.
+    pub fn merge(&mut self, overlay: CulperConfig) {
+        self.me = overlay.me;
+        merge_list(&mut self.targets, overlay.targets, |target| {
+            target.id.clone()
+        });
+        merge_list(&mut self.owners, overlay.owners, |user| {
+            normalize_fingerprint(&user.fingerprint)
+        });
+        merge_list(&mut self.admins, overlay.admins, |user| {
+            normalize_fingerprint(&user.fingerprint)
+        });
+        if overlay.secrets_file.is_some() {
+            self.secrets_file = overlay.secrets_file;
+        }
+        if let Some(secrets) = overlay.secrets {
+            self.secrets
+                .get_or_insert_with(BTreeMap::new)
+                .extend(secrets);
+        }
+    }
+
+    fn sorted(&self) -> CulperConfig {
+        let mut sorted = self.clone();
+        if let Some(targets) = &mut sorted.targets {
+            targets.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        for users in sorted.owners.iter_mut().chain(sorted.admins.iter_mut()) {
+            users.sort_by_key(|user| normalize_fingerprint(&user.fingerprint));
+        }
+        sorted
+    }
+
+    /// The configured targets; a missing list iterates as empty.
+    pub fn iter_targets(&self) -> std::slice::Iter<'_, TargetConfig> {
+        self.targets.as_deref().unwrap_or(&[]).iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a CulperConfig {
+    type Item = &'a TargetConfig;
+    type IntoIter = std::slice::Iter<'a, TargetConfig>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_targets()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct UserConfig {
     pub fingerprint: String,
     pub name: String,
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub added_at: Option<Datetime>,
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub expires_at: Option<Datetime>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// toml's `Datetime` has no floats in it, its equality is total.
+impl Eq for UserConfig {}
+
+/// Hashes the normalized fingerprint only, which identifies the user.
+impl Hash for UserConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        normalize_fingerprint(&self.fingerprint).hash(state);
+    }
+}
+
+impl UserConfig {
+    /// The name, or the last 8 characters of the fingerprint for users without
+    /// one.
+    pub fn display_name(&self) -> String {
+        if !self.name.trim().is_empty() {
+            return self.name.clone();
+        }
+        let fingerprint = normalize_fingerprint(&self.fingerprint);
+        let start = fingerprint
+            .char_indices()
+            .rev()
+            .nth(7)
+            .map_or(0, |(index, _)| index);
+        fingerprint[start..].to_owned()
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.name.trim().is_empty() {
+            return Err(format_err!("User name must not be empty."));
+        }
+        let fingerprint = normalize_fingerprint(&self.fingerprint);
+        if fingerprint.is_empty() || !fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format_err!(
+                "Invalid fingerprint for {}: {}",
+                self.name,
+                self.fingerprint
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TargetConfig {
     pub id: String,
     pub host: String,
+    pub port: Option<u16>,
+    pub format: Option<String>,
+    /// A note for operators, e.g. "decommission after Q3".
+    pub description: Option<String>,
+    /// Labels for grouping targets, e.g. "production" or "eu".
+    pub tags: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone)]
+/// `id: host[:port]`, followed by the description in parentheses if there is one.
+impl fmt::Display for TargetConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}: {}", self.id, self.address(port))?,
+            None => write!(f, "{}: {}", self.id, self.host)?,
+        }
+        match &self.description {
+            Some(description) => write!(f, " ({})", description),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Hashes the identifying id and host.
+impl Hash for TargetConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.host.hash(state);
+    }
+}
+
+impl TargetConfig {
+    /// `host:port`, falling back to `default_port`. IPv6 literals get bracketed.
+    pub fn address(&self, default_port: u16) -> String {
+        let port = self.port.unwrap_or(default_port);
+        if self.host.contains(':') && !self.host.starts_with('[') {
+            format!("[{}]:{}", self.host, port)
+        } else {
+            format!("{}:{}", self.host, port)
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.id.trim().is_empty() {
+            return Err(format_err!("Target id must not be empty."));
+        }
+        if self.host.trim().is_empty() || self.host.contains(char::is_whitespace) {
+            return Err(format_err!(
+                "Invalid host for target {}: {:?}",
+                self.id,
+                self.host
+            ));
+        }
+        if let Some(format) = &self.format {
+            EncryptionFormat::from_str(format)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
 pub struct ConfigReader {
-    pub path: PathBuf,
+    pub path: Option<PathBuf>,
     pub config: Option<CulperConfig>,
+    modified: Option<SystemTime>,
+    interpolate_env: bool,
+    path_source: ConfigPathSource,
+    // Set while a `ConfigLockGuard` from this reader is alive.
+    lock_held: Arc<AtomicBool>,
+}
+
+impl Clone for ConfigReader {
+    /// A clone doesn't share the original's lock: its writes lock the file
+    /// themselves while the original holds a guard.
+    fn clone(&self) -> ConfigReader {
+        ConfigReader {
+            path: self.path.clone(),
+            config: self.config.clone(),
+            modified: self.modified,
+            interpolate_env: self.interpolate_env,
+            path_source: self.path_source,
+            lock_held: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// Where a `ConfigReader` got its path from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigPathSource {
+    /// Passed in, e.g. from `--config_file`, or set with `set_path`.
+    Explicit,
+    /// Taken from `CULPER_CONFIG`.
+    EnvVar,
+    /// `.culper.toml` in the home directory.
+    HomeDefault,
+    /// Not backed by a file at all.
+    Detached,
 }
 
 impl ConfigReader {
+    /// Reads from the path in `CULPER_CONFIG` when it's set and non-empty,
+    /// from the default path otherwise. A missing file is reported by `read`.
+    pub fn from_env_or_default() -> ConfigReader {
+        match ::std::env::var(CONFIG_ENV_VAR) {
+            Ok(ref path) if !path.is_empty() => {
+                let mut config_reader = ConfigReader::new(Some(path));
+                config_reader.path_source = ConfigPathSource::EnvVar;
+                config_reader
+            }
+            _ => ConfigReader::new(None),
+        }
+    }
+
+    /// Uses `raw_config_path` if given, `~/.culper.toml` otherwise.
     pub fn new(raw_config_path: Option<&str>) -> ConfigReader {
-        let config_path = match raw_config_path {
-            Some(val) => PathBuf::from(val),
-            None => get_config_path(),
+        let (config_path, path_source) = match raw_config_path {
+            Some(val) => (expand_home(PathBuf::from(val)), ConfigPathSource::Explicit),
+            None => (get_config_path(), ConfigPathSource::HomeDefault),
         };
 
         ConfigReader {
-            path: config_path,
+            path: Some(config_path),
             config: None,
+            modified: None,
+            interpolate_env: false,
+            path_source,
+            lock_held: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    pub fn from_stdin() -> Result<ConfigReader, Error> {
+        let stdin = io::stdin();
+        let handle = stdin.lock();
+        ConfigReader::from_reader(handle)
+    }
+
+    /// Builds a reader without a backing file; `write` on it fails.
+    pub fn from_reader<R: Read>(reader: R) -> Result<ConfigReader, Error> {
+        let mut config_reader = ConfigReader {
+            path: None,
+            config: None,
+            modified: None,
+            interpolate_env: false,
+            path_source: ConfigPathSource::Detached,
+            lock_held: Arc::new(AtomicBool::new(false)),
+        };
+        config_reader.read_from(reader)?;
+        Ok(config_reader)
+    }
+
     pub fn read(&mut self) -> Result<CulperConfig, Error> {
-        if !&self.path.exists() {
-            return Err(format_err!(
+        let file = self.open()?;
+        let modified = file.metadata()?.modified().ok();
+        let config = self.read_from(file)?;
+        self.modified = modified;
+        Ok(config)
+    }
+
+    /// The file contents exactly as they are on disk, without parsing them.
+    pub fn read_raw(&self) -> Result<String, Error> {
+        let mut raw = String::new();
+        self.open()?.read_to_string(&mut raw)?;
+        Ok(raw)
+    }
+
+    fn open(&self) -> Result<File, Error> {
+        let path = self.require_path()?;
+        File::open(path).map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => format_err!(
                 "{} not found. Create one or pass the --config_file option.",
-                &self
-                    .path
-                    .to_str()
-                    .expect("Failed converting path to string.")
-            ));
+                path.to_str().expect("Failed converting path to string.")
+            ),
+            _ => format_err!(
+                "Could not open configuration file {} ({:?}): {}",
+                path.display(),
+                error.kind(),
+                error
+            ),
+        })
+    }
+
+    /// Re-reads the file if its modification time changed since the last `read`.
+    /// Returns whether a reload happened.
+    pub fn reload_if_changed(&mut self) -> Result<bool, Error> {
+        let modified = fs::metadata(self.require_path()?)?.modified()?;
+        if self.config.is_some() && self.modified == Some(modified) {
+            return Ok(false);
+        }
+
+        self.read()?;
+        Ok(true)
+    }
+
+    /// Reads the config and fails if `validate` reports any errors. Warnings are
+    /// not fatal, call `validate` on the result to see them.
+    pub fn validate_and_read(&mut self) -> Result<CulperConfig, Error> {
+        let config = self.read()?;
+        let errors: Vec<String> = config
+            .validate()
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| issue.message)
+            .collect();
+        if !errors.is_empty() {
+            return Err(format_err!("Invalid config: {}", errors.join("; ")));
+        }
+        Ok(config)
+    }
+
+    /// Like `read`, but writes `default` and returns it when the file is missing.
+    pub fn read_or_create(&mut self, default: CulperConfig) -> Result<CulperConfig, Error> {
+        if self.require_path()?.exists() {
+            return self.read();
         }
 
+        self.update(default.clone()).write()?;
+        Ok(default)
+    }
+
+    pub fn read_from<R: Read>(&mut self, mut reader: R) -> Result<CulperConfig, Error> {
         let mut raw_toml = String::new();
-        File::open(&self.path)
-            .context("Could not open configuration file")?
+        reader
             .read_to_string(&mut raw_toml)
             .context("Could not read configuration file")?;
 
         let config = self.read_string_to_config(&raw_toml)?;
         self.config = Some(config.clone());
+        self.modified = None;
         Ok(config)
     }
 
-    pub fn add_target(&mut self, host: &str, id: &str) -> Result<(), Error> {
+    /// Reads the config at `path` and merges it over the loaded one, see
+    /// `CulperConfig::merge`.
+    pub fn merge_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(format_err!("Overlay config {} not found.", path.display()));
+        }
+        let raw_toml = fs::read_to_string(path)
+            .context(format!("Could not read overlay config {}", path.display()))?;
+        let overlay = self.read_string_to_config(&raw_toml)?;
+        self.loaded_config_mut()?.merge(overlay);
+        Ok(())
+    }
+
+    /// Adds a target, or updates the host of the one with the same `id`, and
+    /// returns the stored entry along with `true` if it was added, `false` if
+    /// an existing one was updated.
+    pub fn add_target(&mut self, host: &str, id: &str) -> Result<(&TargetConfig, bool), Error> {
+        let added = !self.targets().iter().any(|target| target.id == id);
+        self.add_targets(vec![(host.to_owned(), id.to_owned())])?;
+        let target = self
+            .config
+            .iter()
+            .flat_map(CulperConfig::iter_targets)
+            .find(|target| target.id == id)
+            .ok_or_else(|| format_err!("Target {} was not added.", id))?;
+        Ok((target, added))
+    }
+
+    /// Adds `(host, id)` pairs like `add_target`. Every entry is validated before
+    /// any is inserted, so a bad entry leaves the config untouched.
+    pub fn add_targets<I>(&mut self, targets: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let targets: Vec<TargetConfig> = targets
+            .into_iter()
+            .map(|(host, id)| TargetConfig {
+                host,
+                id,
+                port: None,
+                format: None,
+                description: None,
+                tags: None,
+            })
+            .collect();
+        for (index, target) in targets.iter().enumerate() {
+            target
+                .validate()
+                .map_err(|err| format_err!("Invalid target at position {}: {}", index, err))?;
+        }
+
+        match &mut self.config {
+            Some(ref mut config) => {
+                let existing = config.targets.get_or_insert_with(Vec::new);
+                for target in targets {
+                    match existing.iter_mut().find(|known| known.id == target.id) {
+                        Some(known) => known.host = target.host,
+                        None => existing.push(target),
+                    }
+                }
+                Ok(())
+            }
+            None => Err(format_err!("Config is not set.")),
+        }
+    }
+
+    pub fn set_me(&mut self, me: UserConfig) -> Result<(), Error> {
+        me.validate()?;
+        self.loaded_config_mut()?.me = me;
+        Ok(())
+    }
+
+    pub fn clear_targets(&mut self) -> Result<(), Error> {
+        self.loaded_config_mut()?.targets = None;
+        Ok(())
+    }
+
+    pub fn clear_owners(&mut self) -> Result<(), Error> {
+        self.loaded_config_mut()?.owners = None;
+        Ok(())
+    }
+
+    pub fn clear_admins(&mut self) -> Result<(), Error> {
+        self.loaded_config_mut()?.admins = None;
+        Ok(())
+    }
+
+    /// Removes every target whose host matches case-insensitively and returns how
+    /// many were removed.
+    pub fn remove_target_by_host(&mut self, host: &str) -> Result<usize, Error> {
+        match &mut self.config {
+            Some(CulperConfig {
+                targets: Some(ref mut targets),
+                ..
+            }) => {
+                let before = targets.len();
+                targets.retain(|target| !target.host.eq_ignore_ascii_case(host));
+                Ok(before - targets.len())
+            }
+            _ => Ok(0),
+        }
+    }
+
+    /// Changes a target's id, keeping everything else. Returns whether `old_id` was found.
+    pub fn rename_target(&mut self, old_id: &str, new_id: &str) -> Result<bool, Error> {
         match &mut self.config {
-            Some(ref mut config) => match config.targets {
-                None => {
-                    config.targets = Some(vec![TargetConfig {
-                        host: host.to_owned(),
-                        id: id.to_owned(),
-                    }]);
-                    Ok(())
+            Some(CulperConfig {
+                targets: Some(ref mut targets),
+                ..
+            }) => {
+                if old_id != new_id && targets.iter().any(|target| target.id == new_id) {
+                    return Err(format_err!("A target with id {} already exists.", new_id));
+                }
+                match targets.iter_mut().find(|target| target.id == old_id) {
+                    Some(target) => {
+                        target.id = new_id.to_owned();
+                        Ok(true)
+                    }
+                    None => Ok(false),
                 }
-                Some(ref mut targets) => {
-                    targets.push(TargetConfig {
-                        host: host.to_owned(),
-                        id: id.to_owned(),
-                    });
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub fn target_ids(&self) -> Vec<String> {
+        self.targets()
+            .iter()
+            .map(|target| target.id.clone())
+            .collect()
+    }
+
+    /// Targets whose id or host matches the glob `pattern`, e.g. `web-*` or
+    /// `*.example.com`. Hosts match case-insensitively, like in
+    /// `remove_target_by_host`.
+    pub fn find_targets(&self, pattern: &str) -> Result<Vec<&TargetConfig>, Error> {
+        let glob = Pattern::new(pattern)
+            .map_err(|err| format_err!("Invalid target pattern {}: {}", pattern, err))?;
+        let host_options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        Ok(self
+            .targets()
+            .iter()
+            .filter(|target| {
+                glob.matches(&target.id) || glob.matches_with(&target.host, host_options)
+            })
+            .collect())
+    }
 
-                    Ok(())
+    /// Targets bucketed by tag; a target with several tags is in each of their
+    /// buckets. Untagged targets are under `""`.
+    pub fn targets_grouped_by_tag(&self) -> BTreeMap<String, Vec<&TargetConfig>> {
+        let mut groups: BTreeMap<String, Vec<&TargetConfig>> = BTreeMap::new();
+        for target in self.targets() {
+            match &target.tags {
+                Some(tags) if !tags.is_empty() => {
+                    for tag in tags.iter().collect::<BTreeSet<_>>() {
+                        groups.entry(tag.clone()).or_default().push(target);
+                    }
                 }
-            },
-            None => Err(format_err!("Config is not set.")),
+                _ => groups.entry(String::new()).or_default().push(target),
+            }
+        }
+        groups
+    }
+
+    /// Hosts shared by more than one target, with the ids pointing at them.
+    pub fn host_collisions(&self) -> Vec<(String, Vec<String>)> {
+        let mut ids_by_host: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for target in self.targets() {
+            ids_by_host
+                .entry(&target.host)
+                .or_default()
+                .push(target.id.clone());
+        }
+        ids_by_host
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(host, ids)| (host.to_owned(), ids))
+            .collect()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// True when the config file doesn't exist yet, as opposed to existing but
+    /// failing to read. Readers without a file are never on their first run.
+    pub fn first_run(&self) -> bool {
+        match &self.path {
+            Some(path) => !path.exists(),
+            None => false,
+        }
+    }
+
+    /// Points the reader at another file, expanding `~` like `new` does.
+    pub fn set_path(&mut self, path: impl Into<PathBuf>) {
+        self.path = Some(expand_home(path.into()));
+        self.path_source = ConfigPathSource::Explicit;
+        self.modified = None;
+    }
+
+    pub fn config_path_source(&self) -> ConfigPathSource {
+        self.path_source
+    }
+
+    pub fn targets(&self) -> &[TargetConfig] {
+        match &self.config {
+            Some(CulperConfig {
+                targets: Some(targets),
+                ..
+            }) => targets,
+            _ => &[],
+        }
+    }
+
+    pub fn owners(&self) -> &[UserConfig] {
+        match &self.config {
+            Some(CulperConfig {
+                owners: Some(owners),
+                ..
+            }) => owners,
+            _ => &[],
+        }
+    }
+
+    pub fn admins(&self) -> &[UserConfig] {
+        match &self.config {
+            Some(CulperConfig {
+                admins: Some(admins),
+                ..
+            }) => admins,
+            _ => &[],
         }
     }
 
+    pub fn config_ref(&self) -> Option<&CulperConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn config_mut(&mut self) -> Option<&mut CulperConfig> {
+        self.config.as_mut()
+    }
+
+    /// Resolves `${VAR}` and `${VAR:-default}` in string values on read.
+    pub fn with_env_interpolation(&mut self, enabled: bool) -> &mut Self {
+        self.interpolate_env = enabled;
+        self
+    }
+
     pub fn update(&mut self, new_config: CulperConfig) -> &mut Self {
         self.config = Some(new_config);
         self
     }
 
+    /// Exactly what `write` would put on disk: the config as TOML, with targets
+    /// sorted by id and users by fingerprint so equal configs always render to
+    /// the same bytes. If the file exists, the config is merged into it so its
+    /// comments and formatting are kept wherever the config didn't change. A
+    /// file that isn't valid TOML is an error rather than being overwritten.
+    pub fn render(&self) -> Result<String, Error> {
+        let rendered = match &self.config {
+            Some(config) => toml::to_string(&config.sorted())?,
+            None => return Err(format_err!("No config available to write.")),
+        };
+        let existing = self
+            .path
+            .as_ref()
+            .and_then(|path| Some((path, fs::read_to_string(path).ok()?)));
+        match existing {
+            Some((path, ref existing)) if !existing.trim().is_empty() => {
+                edit::merge_into(existing, &rendered).map_err(|error| {
+                    format_err!(
+                        "Could not merge into {}, fix or remove it first: {}",
+                        path.display(),
+                        error
+                    )
+                })
+            }
+            _ => Ok(rendered),
+        }
+    }
+
+    /// Writes the config to its file. Comments and formatting already in the
+    /// file are kept wherever the config didn't change.
     pub fn write(&self) -> Result<(), Error> {
-        match &self.config {
-            Some(config) => {
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .open(&self.path)?
-                    .write_all(toml::to_string(&config)?.as_bytes())?;
-                Ok(())
+        let _lock = self.lock_for_write()?;
+        let (path, rendered) = self.prepare_write()?;
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?
+            .write_all(rendered.as_bytes())?;
+        Ok(())
+    }
+
+    /// Like `write`, but goes through a temp file that is renamed over the
+    /// config, so readers never see a half-written file. On Unix the temp file
+    /// is created with mode `0600`, the config is never readable by others.
+    pub fn write_atomic_with_perms(&self) -> Result<(), Error> {
+        let _lock = self.lock_for_write()?;
+        let (path, rendered) = self.prepare_write()?;
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| format_err!("Config path {} has no file name.", path.display()))?;
+        let temp_path = path.with_file_name(format!(
+            ".{}.tmp-{}",
+            file_name.to_string_lossy(),
+            ::std::process::id()
+        ));
+
+        let written = create_private_file(&temp_path).and_then(|mut file| {
+            file.write_all(rendered.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&temp_path, path)?;
+            Ok(())
+        });
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        written
+    }
+
+    // The path to write to, with parent directories in place, and what `render`
+    // returns for it.
+    fn prepare_write(&self) -> Result<(&Path, String), Error> {
+        let rendered = self.render()?;
+        let path = self.require_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok((path, rendered))
+    }
+
+    fn loaded_config_mut(&mut self) -> Result<&mut CulperConfig, Error> {
+        match &mut self.config {
+            Some(config) => Ok(config),
+            None => Err(format_err!("Config is not set.")),
+        }
+    }
+
+    fn require_path(&self) -> Result<&Path, Error> {
+        match &self.path {
+            Some(path) => Ok(path),
+            None => Err(format_err!("This config reader is not backed by a file.")),
+        }
+    }
+
+    /// Whether the loaded config, or else the file, has an identity table.
+    /// Unlike `read`, a file without one is just `false` rather than an error.
+    pub fn has_identity(&self) -> bool {
+        if self.config.is_some() {
+            return true;
+        }
+        match self
+            .read_raw()
+            .ok()
+            .and_then(|raw| raw.parse::<toml::Value>().ok())
+        {
+            Some(toml::Value::Table(table)) => {
+                table.contains_key("identity") || table.contains_key("me")
             }
-            None => Err(format_err!("No config available to write.")),
+            _ => false,
         }
     }
 
     fn read_string_to_config(&self, string: &str) -> Result<CulperConfig, Error> {
-        let parsed_toml: CulperConfig = toml::from_str(&string)?;
-        Ok(parsed_toml)
+        if !self.interpolate_env {
+            return toml::from_str(string).map_err(describe_toml_error);
+        }
+
+        let mut value: toml::Value = string.parse().map_err(describe_toml_error)?;
+        interpolate::interpolate_env(&mut value)?;
+        value.try_into().map_err(describe_toml_error)
+    }
+}
+
+fn describe_toml_error(error: toml::de::Error) -> Error {
+    if error.to_string() == "missing field `identity`" {
+        return format_err!(
+            "invalid config: the [identity] table is missing. Run culper init to create one."
+        );
+    }
+    match error.line_col() {
+        Some((line, column)) => {
+            // toml appends the line itself, drop it so it isn't reported twice.
+            let message = error.to_string();
+            let suffix = format!(" at line {}", line + 1);
+            format_err!(
+                "invalid config at line {}, column {}: {}",
+                line + 1,
+                column + 1,
+                message.trim_end_matches(suffix.as_str())
+            )
+        }
+        None => format_err!("invalid config: {}", error),
+    }
+}
+
+/// Strips whitespace and uppercases, so `1234 abcd` and `1234ABCD` compare equal.
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
+
+pub fn get_config_path() -> PathBuf {
+    default_config_path(dirs::home_dir())
+}
+
+fn create_private_file(path: &Path) -> Result<File, Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
     }
+    Ok(options.open(path)?)
 }
 
-fn get_config_path() -> PathBuf {
+/// `.culper.toml` inside `home`, or in the working directory without one.
+pub fn default_config_path(home: Option<PathBuf>) -> PathBuf {
     let mut path = PathBuf::new();
-    match dirs::home_dir() {
+    match home {
         Some(home) => path.push(home),
         None => path.push("./"),
     };
@@ -126,55 +956,1263 @@ fn get_config_path() -> PathBuf {
 }
 
 pub fn create(name: String, fingerprint: String, config_path: String) -> Result<(), Error> {
+    create_with_user(
+        UserConfig {
+            name,
+            fingerprint,
+            added_at: None,
+            expires_at: None,
+        },
+        &config_path,
+    )
+}
+
+pub fn create_with_user(me: UserConfig, config_path: &str) -> Result<(), Error> {
+    create_config(me, None, config_path)
+}
+
+/// Like `create_with_user`, but also lists `me` as the first owner, so the new
+/// config can seal to the operator right away.
+pub fn create_with_self_owner(me: UserConfig, config_path: &str) -> Result<(), Error> {
+    let owners = vec![me.clone()];
+    create_config(me, Some(owners), config_path)
+}
+
+fn create_config(
+    me: UserConfig,
+    owners: Option<Vec<UserConfig>>,
+    config_path: &str,
+) -> Result<(), Error> {
+    me.validate()?;
     let config = CulperConfig {
-        me: UserConfig { name, fingerprint },
+        me,
         targets: None,
-        owners: None,
+        owners,
         admins: None,
+        secrets: None,
+        secrets_file: None,
     };
-    File::create(config_path)?.write_all(toml::to_string(&config)?.as_bytes())?;
+    let path = Path::new(config_path);
+    if path.is_dir() {
+        return Err(format_err!(
+            "{} is a directory, pass the path of the config file to create.",
+            path.display()
+        ));
+    }
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).map_err(|error| {
+            format_err!("Could not create directory {}: {}", parent.display(), error)
+        })?;
+    }
+    let mut file = File::create(path).map_err(|error| {
+        format_err!(
+            "Could not create configuration file {} ({:?}): {}",
+            path.display(),
+            error.kind(),
+            error
+        )
+    })?;
+    file.write_all(toml::to_string(&config)?.as_bytes())?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
     use std::path::Path;
 
     #[test]
     fn can_create_config() {
+        let path = temp_config_path("create");
         create(
             "test@test.de".to_owned(),
             "12345678".to_owned(),
-            "./culper.toml".to_owned(),
+            path.to_str().unwrap().to_owned(),
         )
         .unwrap();
-        assert!(Path::new("./culper.toml").exists());
+        let created = path.exists();
+        remove_temp_config(&path);
+        assert!(created);
     }
 
     #[test]
-    fn can_update_existing_config() {
-        let mut config_reader = ConfigReader::new(Some("./culper.toml"));
+    fn get_config_path_matches_the_default_reader() {
+        assert_eq!(
+            Some(get_config_path().as_path()),
+            ConfigReader::new(None).path()
+        );
+    }
 
-        config_reader.update(CulperConfig {
-            me: UserConfig {
-                name: "overwrite@mail.de".to_owned(),
-                fingerprint: "1234 5678 ABCD ETC".to_owned(),
-            },
-            targets: None,
-            owners: None,
-            admins: None,
-        });
+    #[test]
+    fn create_makes_missing_parent_directories() {
+        let root = temp_config_path("create-nested");
+        let path = root.join("a").join("b").join("culper.toml");
+        create_with_user(test_config().me, path.to_str().unwrap()).unwrap();
+        let created = path.exists();
+        fs::remove_dir_all(&root).unwrap();
+        assert!(created);
+    }
+
+    #[test]
+    fn create_rejects_a_directory() {
+        let path = temp_config_path("create-dir");
+        fs::create_dir_all(&path).unwrap();
+        let error = create_with_user(test_config().me, path.to_str().unwrap())
+            .err()
+            .unwrap()
+            .to_string();
+        fs::remove_dir_all(&path).unwrap();
+        assert!(error.ends_with(" is a directory, pass the path of the config file to create."));
+    }
+
+    #[test]
+    fn create_and_create_with_user_write_identical_files() {
+        let by_strings = temp_config_path("create-strings");
+        let by_user = temp_config_path("create-user");
+        create(
+            "test@test.de".to_owned(),
+            "12345678".to_owned(),
+            by_strings.to_str().unwrap().to_owned(),
+        )
+        .unwrap();
+        create_with_user(test_config().me, by_user.to_str().unwrap()).unwrap();
+
+        let by_strings_contents = fs::read_to_string(&by_strings).unwrap();
+        let by_user_contents = fs::read_to_string(&by_user).unwrap();
+        remove_temp_config(&by_strings);
+        remove_temp_config(&by_user);
+        assert_eq!(by_strings_contents, by_user_contents);
+    }
+
+    #[test]
+    fn create_with_self_owner_lists_me_as_owner() {
+        let path = temp_config_path("create-self-owner");
+        create_with_self_owner(test_config().me, path.to_str().unwrap()).unwrap();
+
+        let mut config_reader = ConfigReader::new(path.to_str());
+        let config = config_reader.read().unwrap();
+        remove_temp_config(&path);
+        assert_eq!(config_reader.owners(), &[config.me]);
+    }
+
+    #[test]
+    fn create_rejects_invalid_user() {
+        let path = temp_config_path("create-invalid");
+        let result = create(
+            "test@test.de".to_owned(),
+            "not a fingerprint".to_owned(),
+            path.to_str().unwrap().to_owned(),
+        );
+        assert!(result.is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn can_update_existing_config() {
+        let path = temp_config_path("update");
+        let mut config_reader = ConfigReader::new(path.to_str());
+
+        config_reader.update(CulperConfig {
+            me: UserConfig {
+                name: "overwrite@mail.de".to_owned(),
+                fingerprint: "1234 5678 ABCD ETC".to_owned(),
+                added_at: None,
+                expires_at: None,
+            },
+            targets: None,
+            owners: None,
+            admins: None,
+            secrets: None,
+            secrets_file: None,
+        });
 
         config_reader
             .add_target("www.test.de", "alskjdflsajfd")
             .unwrap();
         config_reader.write().unwrap();
 
-        let mut file = OpenOptions::new().read(true).open("./culper.toml").unwrap();
+        let mut file = OpenOptions::new().read(true).open(&path).unwrap();
         let mut contents = String::new();
         file.read_to_string(&mut contents).unwrap();
+        let rendered = config_reader.render().unwrap();
+        remove_temp_config(&path);
+
+        assert_eq!(contents, rendered)
+    }
+
+    #[test]
+    fn first_run_means_no_file_yet() {
+        let path = temp_config_path("first-run");
+        let config_reader = ConfigReader::new(path.to_str());
+        assert!(config_reader.first_run());
+
+        fs::write(&path, "not toml at all [").unwrap();
+        assert!(!config_reader.first_run());
+        remove_temp_config(&path);
+
+        assert!(
+            !ConfigReader::from_reader(toml::to_string(&test_config()).unwrap().as_bytes())
+                .unwrap()
+                .first_run()
+        );
+    }
+
+    #[test]
+    fn merges_overlay_files() {
+        let path = temp_config_path("overlay");
+        fs::write(
+            &path,
+            "[me]\nname = \"overlay@test.de\"\nfingerprint = \"ABCDEF01\"\n\n[[targets]]\nid = \"db\"\nhost = \"db.test.de\"\n\n[[targets]]\nid = \"web\"\nhost = \"new.test.de\"\n",
+        )
+        .unwrap();
+
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.merge_file(&path).unwrap();
+        remove_temp_config(&path);
+
+        let config = config_reader.config_ref().unwrap();
+        assert_eq!(config.me.name, "overlay@test.de");
+        let targets: Vec<(&str, &str)> = config
+            .iter_targets()
+            .map(|target| (target.id.as_str(), target.host.as_str()))
+            .collect();
+        assert_eq!(targets, vec![("web", "new.test.de"), ("db", "db.test.de")]);
+
+        let error = config_reader.merge_file(&path).err().unwrap().to_string();
+        assert!(error.starts_with("Overlay config "));
+        assert!(error.ends_with(" not found."));
+    }
+
+    #[test]
+    fn read_raw_returns_file_verbatim() {
+        let path = temp_config_path("read_raw");
+        let contents =
+            "# managed by ops\n[me]\nfingerprint = \"ABC\"  # primary key\nname = \"me\"\n";
+        fs::write(&path, contents).unwrap();
+
+        let config_reader = ConfigReader::new(Some(path.to_str().unwrap()));
+        let raw = config_reader.read_raw().unwrap();
+        remove_temp_config(&path);
+        assert_eq!(raw, contents);
+
+        let error = config_reader.read_raw().err().unwrap().to_string();
+        assert!(error.ends_with(" not found. Create one or pass the --config_file option."));
+    }
+
+    #[test]
+    fn reads_renamed_keys_under_their_old_name() {
+        let config_reader = ConfigReader::new(None);
+        let old = config_reader
+            .read_string_to_config("[me]\nname = \"test@test.de\"\nfingerprint = \"12345678\"\n")
+            .unwrap();
+        assert_eq!(old.me.name, "test@test.de");
+
+        let rendered = toml::to_string(&old).unwrap();
+        assert!(rendered.starts_with("[identity]\n"));
+        let new = config_reader.read_string_to_config(&rendered).unwrap();
+        assert_eq!(new.me, old.me);
+    }
+
+    #[test]
+    fn missing_identity_asks_for_init() {
+        let path = temp_config_path("no-identity");
+        fs::write(&path, "[[targets]]\nid = \"web\"\nhost = \"www.test.de\"\n").unwrap();
+
+        let mut config_reader = ConfigReader::new(path.to_str());
+        assert!(!config_reader.has_identity());
+        let error = config_reader.read().err().unwrap().to_string();
+        assert_eq!(
+            error,
+            "invalid config: the [identity] table is missing. Run culper init to create one."
+        );
+
+        fs::write(
+            &path,
+            "[me]\nname = \"test@test.de\"\nfingerprint = \"12345678\"\n",
+        )
+        .unwrap();
+        assert!(config_reader.has_identity());
+        config_reader.read().unwrap();
+        remove_temp_config(&path);
+        assert!(config_reader.has_identity());
+    }
+
+    #[test]
+    fn write_keeps_comments_in_existing_file() {
+        let path = temp_config_path("comments");
+        fs::write(
+            &path,
+            "# Managed by hand, please keep tidy.\n\n[me]\nname = \"test@test.de\" # primary identity\nfingerprint = \"12345678\"\n\n# Production hosts\n[[targets]]\nid = \"web\"\nhost = \"www.test.de\"\n",
+        )
+        .unwrap();
+
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.read().unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+        config_reader.write().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("# Managed by hand, please keep tidy.\n"));
+        assert!(contents.contains("name = \"test@test.de\" # primary identity\n"));
+        assert!(contents.contains("# Production hosts\n[[targets]]\nid = \"web\"\n"));
+        assert_eq!(config_reader.read().unwrap().iter_targets().count(), 2);
+        remove_temp_config(&path);
+    }
+
+    #[test]
+    fn equal_configs_render_identically() {
+        let path = temp_config_path("stable-order");
+        let mut forwards = ConfigReader::new(path.to_str());
+        forwards.update(test_config());
+        forwards.add_target("www.test.de", "web").unwrap();
+        forwards.add_target("db.test.de", "db").unwrap();
+        forwards.write().unwrap();
+        let first = fs::read(&path).unwrap();
+        remove_temp_config(&path);
+
+        let mut backwards = ConfigReader::new(path.to_str());
+        backwards.update(test_config());
+        backwards.add_target("db.test.de", "db").unwrap();
+        backwards.add_target("www.test.de", "web").unwrap();
+        backwards.write().unwrap();
+        backwards.write().unwrap();
+        let second = fs::read(&path).unwrap();
+        remove_temp_config(&path);
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn write_refuses_to_overwrite_a_broken_file() {
+        let path = temp_config_path("broken");
+        fs::write(&path, "# keep me\nnot toml at all [").unwrap();
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(test_config());
+
+        let error = config_reader.write().err().unwrap().to_string();
+        let contents = fs::read_to_string(&path).unwrap();
+        remove_temp_config(&path);
+        assert!(error.starts_with("Could not merge into "));
+        assert_eq!(contents, "# keep me\nnot toml at all [");
+
+        fs::write(&path, "\n").unwrap();
+        config_reader.write().unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        remove_temp_config(&path);
+        assert_eq!(written, config_reader.render().unwrap());
+    }
+
+    #[test]
+    fn render_matches_written_file() {
+        let path = temp_config_path("render");
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(test_config());
+        config_reader
+            .add_target("www.test.de", "alskjdflsajfd")
+            .unwrap();
+
+        let rendered = config_reader.render().unwrap();
+        config_reader.write().unwrap();
+        let fresh = fs::read_to_string(&path).unwrap();
+        assert_eq!(rendered, fresh);
+
+        fs::write(&path, format!("# hand comment\n{}", fresh)).unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+        let rendered = config_reader.render().unwrap();
+        config_reader.write().unwrap();
+        let merged = fs::read_to_string(&path).unwrap();
+        remove_temp_config(&path);
+        assert!(rendered.starts_with("# hand comment\n"));
+        assert_eq!(rendered, merged);
+    }
+
+    #[test]
+    fn all_fingerprints_are_normalized_and_deduplicated() {
+        let mut config = test_config();
+        config.owners = Some(vec![
+            UserConfig {
+                name: "owner@test.de".to_owned(),
+                fingerprint: "1234 5678".to_owned(),
+                added_at: None,
+                expires_at: None,
+            },
+            UserConfig {
+                name: "other@test.de".to_owned(),
+                fingerprint: "abcd ef01".to_owned(),
+                added_at: None,
+                expires_at: None,
+            },
+        ]);
+        config.admins = Some(vec![UserConfig {
+            name: "admin@test.de".to_owned(),
+            fingerprint: "ABCDEF01".to_owned(),
+            added_at: None,
+            expires_at: None,
+        }]);
+
+        let fingerprints: Vec<String> = config.all_fingerprints().into_iter().collect();
+        assert_eq!(fingerprints, vec!["12345678", "ABCDEF01"]);
+    }
+
+    #[test]
+    fn can_read_config_from_reader() {
+        let raw_toml = ::toml::to_string(&test_config()).unwrap();
+        let config_reader = ConfigReader::from_reader(::std::io::Cursor::new(raw_toml)).unwrap();
+
+        assert_eq!(
+            config_reader.config.as_ref().unwrap().me.name,
+            "test@test.de"
+        );
+        assert!(config_reader.path.is_none());
+        assert!(config_reader.write().is_err());
+    }
+
+    #[test]
+    fn reports_hosts_shared_by_multiple_targets() {
+        let mut config_reader = ConfigReader::new(None);
+        assert!(config_reader.target_ids().is_empty());
+
+        config_reader.update(test_config());
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+        config_reader.add_target("www.test.de", "web-old").unwrap();
+
+        assert_eq!(config_reader.target_ids(), vec!["web", "db", "web-old"]);
+        assert_eq!(
+            config_reader.host_collisions(),
+            vec![(
+                "www.test.de".to_owned(),
+                vec!["web".to_owned(), "web-old".to_owned()]
+            )]
+        );
+    }
+
+    #[test]
+    fn read_or_create_writes_default_when_missing() {
+        let path = temp_config_path("read-or-create-missing");
+        let _ = ::std::fs::remove_file(&path);
+        let mut config_reader = ConfigReader::new(path.to_str());
+
+        let config = config_reader.read_or_create(test_config()).unwrap();
+        let created = path.exists();
+        remove_temp_config(&path);
+        assert_eq!(config.me.name, "test@test.de");
+        assert!(created);
+    }
+
+    #[test]
+    fn read_or_create_keeps_existing_file() {
+        let path = temp_config_path("read-or-create-present");
+        let mut existing = test_config();
+        existing.me.name = "existing@test.de".to_owned();
+        ConfigReader::new(path.to_str())
+            .update(existing)
+            .write()
+            .unwrap();
+
+        let config = ConfigReader::new(path.to_str())
+            .read_or_create(test_config())
+            .unwrap();
+        remove_temp_config(&path);
+        assert_eq!(config.me.name, "existing@test.de");
+    }
+
+    #[test]
+    fn add_target_returns_the_stored_target() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+
+        let (added, was_added) = config_reader.add_target("www.test.de", "web").unwrap();
+        assert!(was_added);
+        assert_eq!(
+            (added.id.as_str(), added.host.as_str()),
+            ("web", "www.test.de")
+        );
+
+        let (updated, was_added) = config_reader.add_target("new.test.de", "web").unwrap();
+        let updated = updated.clone();
+        assert!(!was_added);
+        assert_eq!(updated.host, "new.test.de");
+        assert_eq!(config_reader.targets(), vec![updated]);
+    }
+
+    #[test]
+    fn finds_targets_by_glob() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        config_reader
+            .add_target("web1.example.com", "web-1")
+            .unwrap();
+        config_reader
+            .add_target("web2.example.com", "web-2")
+            .unwrap();
+        config_reader.add_target("db.internal", "db").unwrap();
+
+        let ids = |targets: Vec<&TargetConfig>| -> Vec<String> {
+            targets.iter().map(|target| target.id.clone()).collect()
+        };
+        assert_eq!(
+            ids(config_reader.find_targets("web-*").unwrap()),
+            vec!["web-1", "web-2"]
+        );
+        assert_eq!(
+            ids(config_reader.find_targets("*.EXAMPLE.com").unwrap()),
+            vec!["web-1", "web-2"]
+        );
+        assert_eq!(ids(config_reader.find_targets("db").unwrap()), vec!["db"]);
+        assert!(config_reader.find_targets("web-[").is_err());
+    }
+
+    #[test]
+    fn groups_targets_by_tag() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        config_reader.add_target("web.test.de", "web").unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+        config_reader.add_target("dev.test.de", "dev").unwrap();
+        config_reader.add_target("ci.test.de", "ci").unwrap();
+        let tags = [
+            vec!["production", "eu"],
+            vec!["production"],
+            vec!["eu", "eu"],
+            vec![],
+        ];
+        for (target, tags) in config_reader
+            .config_mut()
+            .unwrap()
+            .targets
+            .iter_mut()
+            .flatten()
+            .zip(&tags)
+        {
+            target.tags = Some(tags.iter().map(|tag| tag.to_string()).collect());
+        }
+
+        let groups: Vec<(String, Vec<&str>)> = config_reader
+            .targets_grouped_by_tag()
+            .into_iter()
+            .map(|(tag, targets)| (tag, targets.iter().map(|t| t.id.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("".to_owned(), vec!["ci"]),
+                ("eu".to_owned(), vec!["web", "dev"]),
+                ("production".to_owned(), vec!["web", "db"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_users_with_roles() {
+        let mut config = test_config();
+        let user = |name: &str, fingerprint: &str| UserConfig {
+            name: name.to_owned(),
+            fingerprint: fingerprint.to_owned(),
+            added_at: None,
+            expires_at: None,
+        };
+        config.owners = Some(vec![user("alice", "AAAA"), user("bob", "bbbb")]);
+        config.admins = Some(vec![user("bob", "BBBB")]);
+
+        let roles: Vec<(&str, Role)> = config
+            .users_with_roles()
+            .map(|(user, role)| (user.name.as_str(), role))
+            .collect();
+        assert_eq!(roles, vec![("bob", Role::Admin), ("alice", Role::Owner)]);
+    }
+
+    #[test]
+    fn removes_targets_by_host() {
+        let mut config_reader = ConfigReader::new(None);
+        assert_eq!(
+            config_reader.remove_target_by_host("www.test.de").unwrap(),
+            0
+        );
+
+        config_reader.update(test_config());
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+        config_reader.add_target("WWW.test.de", "web-old").unwrap();
+
+        assert_eq!(
+            config_reader.remove_target_by_host("www.TEST.de").unwrap(),
+            2
+        );
+        assert_eq!(config_reader.target_ids(), vec!["db"]);
+    }
+
+    #[test]
+    fn renames_targets() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+
+        assert!(config_reader.rename_target("web", "frontend").unwrap());
+        assert_eq!(config_reader.target_ids(), vec!["frontend", "db"]);
+        assert_eq!(config_reader.targets()[0].host, "www.test.de");
+
+        assert!(!config_reader.rename_target("web", "www").unwrap());
+        assert_eq!(
+            config_reader
+                .rename_target("frontend", "db")
+                .err()
+                .unwrap()
+                .to_string(),
+            "A target with id db already exists."
+        );
+        assert_eq!(config_reader.target_ids(), vec!["frontend", "db"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_write_is_private_throughout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut dir = ::std::env::temp_dir();
+        dir.push(format!("culper-lib-atomic-{}", ::std::process::id()));
+        let path = dir.join("culper.toml");
+
+        // The temp file is born private, not chmod-ed after the fact.
+        fs::create_dir_all(&dir).unwrap();
+        let probe = dir.join("probe");
+        create_private_file(&probe).unwrap();
+        assert_eq!(
+            fs::metadata(&probe).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        fs::remove_file(&probe).unwrap();
+
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(test_config());
+        config_reader.write_atomic_with_perms().unwrap();
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.write_atomic_with_perms().unwrap();
+
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        let entries: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains(&dir.join("culper.toml.lock")));
+        assert_eq!(config_reader.read().unwrap().iter_targets().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_errors_name_path_and_kind() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_config_path("unreadable");
+        fs::write(&path, toml::to_string(&test_config()).unwrap()).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let result = ConfigReader::new(path.to_str()).read();
+        remove_temp_config(&path);
+        // Permission bits don't stop root, so there is nothing to check then.
+        if let Err(error) = result {
+            let error = error.to_string();
+            assert!(error.contains(path.to_str().unwrap()));
+            assert!(error.contains("PermissionDenied"));
+        }
+
+        let missing = temp_config_path("missing");
+        let error = ConfigReader::new(missing.to_str())
+            .read()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.ends_with(" not found. Create one or pass the --config_file option."));
+
+        let file = temp_config_path("not-a-dir");
+        fs::write(&file, "").unwrap();
+        let error = ConfigReader::new(file.join("culper.toml").to_str())
+            .read()
+            .err()
+            .unwrap()
+            .to_string();
+        remove_temp_config(&file);
+        assert!(error.contains(file.to_str().unwrap()));
+        assert!(error.contains("NotADirectory"));
+    }
+
+    #[test]
+    fn parse_errors_report_line_and_column() {
+        let broken = "[me]\nname = \"test@test.de\"\nfingerprint\n";
+        let error = ConfigReader::from_reader(broken.as_bytes())
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(error.starts_with("invalid config at line 3, column "));
+        assert_eq!(error.matches("at line").count(), 1);
+    }
+
+    #[test]
+    fn bulk_adds_targets_with_dedup() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        config_reader
+            .add_targets(vec![
+                ("www.test.de".to_owned(), "web".to_owned()),
+                ("db.test.de".to_owned(), "db".to_owned()),
+                ("www2.test.de".to_owned(), "web".to_owned()),
+            ])
+            .unwrap();
+
+        assert_eq!(config_reader.target_ids(), vec!["web", "db"]);
+        assert_eq!(
+            config_reader.config.unwrap().targets.unwrap()[0].host,
+            "www2.test.de"
+        );
+    }
+
+    #[test]
+    fn bulk_add_rejects_invalid_entries() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        let error = config_reader
+            .add_targets(vec![
+                ("www.test.de".to_owned(), "web".to_owned()),
+                ("".to_owned(), "db".to_owned()),
+            ])
+            .err()
+            .unwrap();
+
+        assert!(error
+            .to_string()
+            .starts_with("Invalid target at position 1"));
+        assert!(config_reader.target_ids().is_empty());
+    }
+
+    #[test]
+    fn targets_may_specify_a_format() {
+        let raw_toml = r#"
+            [me]
+            name = "test@test.de"
+            fingerprint = "12345678"
+
+            [[targets]]
+            id = "web"
+            host = "www.test.de"
+            format = "PLAINTEXT"
+
+            [[targets]]
+            id = "db"
+            host = "db.test.de"
+        "#;
+        let config_reader = ConfigReader::from_reader(raw_toml.as_bytes()).unwrap();
+        let config = config_reader.config.unwrap();
+
+        assert_eq!(
+            config.target_format("web"),
+            Some(EncryptionFormat::PLAINTEXT)
+        );
+        assert_eq!(config.target_format("db"), None);
+        assert_eq!(config.target_format("unknown"), None);
+    }
+
+    #[test]
+    fn reloads_only_after_the_file_changed() {
+        let path = temp_config_path("reload");
+        ConfigReader::new(path.to_str())
+            .update(test_config())
+            .write()
+            .unwrap();
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.read().unwrap();
+
+        assert!(!config_reader.reload_if_changed().unwrap());
+        assert!(!config_reader.reload_if_changed().unwrap());
+
+        let later = SystemTime::now() + ::std::time::Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let reloaded = config_reader.reload_if_changed().unwrap();
+        let reloaded_again = config_reader.reload_if_changed().unwrap();
+        remove_temp_config(&path);
+        assert!(reloaded);
+        assert!(!reloaded_again);
+    }
+
+    #[test]
+    fn write_creates_missing_parent_directories() {
+        let root = temp_config_path("nested-write");
+        let _ = fs::remove_dir_all(&root);
+        let path = root.join("culper").join("config.toml");
+
+        ConfigReader::new(path.to_str())
+            .update(test_config())
+            .write()
+            .unwrap();
+        let created = path.exists();
+        fs::remove_dir_all(&root).unwrap();
+        assert!(created);
+    }
+
+    #[test]
+    fn target_address_uses_explicit_port() {
+        let target = TargetConfig {
+            id: "web".to_owned(),
+            host: "10.0.0.1".to_owned(),
+            port: Some(8443),
+            format: None,
+            description: None,
+            tags: None,
+        };
+        assert_eq!(target.address(443), "10.0.0.1:8443");
+    }
+
+    #[test]
+    fn target_address_falls_back_to_default_port() {
+        let target = TargetConfig {
+            id: "web".to_owned(),
+            host: "www.test.de".to_owned(),
+            port: None,
+            format: None,
+            description: None,
+            tags: None,
+        };
+        assert_eq!(target.address(443), "www.test.de:443");
+    }
+
+    #[test]
+    fn target_address_brackets_ipv6_literals() {
+        let target = TargetConfig {
+            id: "web".to_owned(),
+            host: "::1".to_owned(),
+            port: None,
+            format: None,
+            description: None,
+            tags: None,
+        };
+        assert_eq!(target.address(443), "[::1]:443");
+    }
+
+    #[test]
+    fn interpolates_environment_when_enabled() {
+        ::std::env::set_var("CULPER_TEST_CONFIG_NAME", "env@test.de");
+        let raw_toml = "[me]\nname = \"${CULPER_TEST_CONFIG_NAME}\"\nfingerprint = \"12345678\"\n";
+
+        let mut config_reader = ConfigReader::new(None);
+        let literal = config_reader.read_from(raw_toml.as_bytes()).unwrap();
+        assert_eq!(literal.me.name, "${CULPER_TEST_CONFIG_NAME}");
+
+        let resolved = config_reader
+            .with_env_interpolation(true)
+            .read_from(raw_toml.as_bytes())
+            .unwrap();
+        assert_eq!(resolved.me.name, "env@test.de");
+    }
+
+    #[test]
+    fn set_me_replaces_identity_and_persists() {
+        let path = temp_config_path("set-me");
+        let mut config_reader = ConfigReader::new(path.to_str());
+        assert!(config_reader.set_me(test_config().me).is_err());
+
+        config_reader.update(test_config());
+        config_reader
+            .set_me(UserConfig {
+                name: "rotated@test.de".to_owned(),
+                fingerprint: "ABCDEF01".to_owned(),
+                added_at: None,
+                expires_at: None,
+            })
+            .unwrap();
+        config_reader.write().unwrap();
+
+        let me = ConfigReader::new(path.to_str()).read().unwrap().me;
+        remove_temp_config(&path);
+        assert_eq!(me.name, "rotated@test.de");
+        assert_eq!(me.fingerprint, "ABCDEF01");
+    }
+
+    #[test]
+    fn from_env_or_default_prefers_the_env_var() {
+        let path = temp_config_path("from-env");
+        ::std::env::set_var(CONFIG_ENV_VAR, &path);
+        let config_reader = ConfigReader::from_env_or_default();
+        assert_eq!(config_reader.path(), Some(path.as_path()));
+        assert_eq!(config_reader.config_path_source(), ConfigPathSource::EnvVar);
+
+        ::std::env::remove_var(CONFIG_ENV_VAR);
+        assert_eq!(
+            ConfigReader::from_env_or_default().path(),
+            ConfigReader::new(None).path()
+        );
+    }
+
+    #[test]
+    fn reports_where_the_path_came_from() {
+        assert_eq!(
+            ConfigReader::new(Some("./culper.toml")).config_path_source(),
+            ConfigPathSource::Explicit
+        );
+        let detached =
+            ConfigReader::from_reader(toml::to_string(&test_config()).unwrap().as_bytes()).unwrap();
+        assert_eq!(detached.config_path_source(), ConfigPathSource::Detached);
+
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.set_path(temp_config_path("path-source"));
+        assert_eq!(
+            config_reader.config_path_source(),
+            ConfigPathSource::Explicit
+        );
+
+        assert_eq!(
+            ConfigReader::new(None).config_path_source(),
+            ConfigPathSource::HomeDefault
+        );
+    }
+
+    #[test]
+    fn default_config_path_lives_in_home() {
+        assert_eq!(
+            default_config_path(Some(PathBuf::from("/home/culper"))),
+            PathBuf::from("/home/culper/.culper.toml")
+        );
+        assert_eq!(default_config_path(None), PathBuf::from("./.culper.toml"));
+    }
+
+    #[test]
+    fn clears_lists() {
+        let mut config_reader = ConfigReader::new(None);
+        assert!(config_reader.clear_targets().is_err());
+
+        let mut config = test_config();
+        config.owners = Some(vec![config.me.clone()]);
+        config.admins = Some(vec![config.me.clone()]);
+        config_reader.update(config);
+        config_reader.add_target("www.test.de", "web").unwrap();
+
+        config_reader.clear_targets().unwrap();
+        config_reader.clear_owners().unwrap();
+        config_reader.clear_admins().unwrap();
+
+        let config = config_reader.config.as_ref().unwrap();
+        assert!(config.targets.is_none());
+        assert!(config.owners.is_none());
+        assert!(config.admins.is_none());
+        assert_eq!(config.target_format("web"), None);
+    }
+
+    #[test]
+    fn changes_through_config_mut_are_written() {
+        let path = temp_config_path("config-mut");
+        let mut config_reader = ConfigReader::new(path.to_str());
+        assert!(config_reader.config_ref().is_none());
+
+        config_reader.update(test_config());
+        config_reader.config_mut().unwrap().me.name = "mutated@test.de".to_owned();
+        config_reader.write().unwrap();
+
+        let config = ConfigReader::new(path.to_str()).read().unwrap();
+        remove_temp_config(&path);
+        assert_eq!(config.me.name, "mutated@test.de");
+        assert_eq!(
+            config_reader.config_ref().unwrap().me.name,
+            "mutated@test.de"
+        );
+    }
+
+    #[test]
+    fn rotates_fingerprints_everywhere() {
+        let mut config = test_config();
+        config.owners = Some(vec![
+            UserConfig {
+                fingerprint: "1234 5678".to_owned(),
+                ..config.me.clone()
+            },
+            UserConfig {
+                name: "other@test.de".to_owned(),
+                fingerprint: "ABCDEF01".to_owned(),
+                added_at: None,
+                expires_at: None,
+            },
+        ]);
+
+        assert_eq!(
+            config.rotate_fingerprint("12345678", "9abc def0").unwrap(),
+            2
+        );
+        assert_eq!(config.me.fingerprint, "9ABCDEF0");
+        assert_eq!(config.owners.as_ref().unwrap()[0].fingerprint, "9ABCDEF0");
+        assert_eq!(config.owners.as_ref().unwrap()[1].fingerprint, "ABCDEF01");
+        assert!(config.rotate_fingerprint("9ABCDEF0", "not hex").is_err());
+    }
+
+    #[test]
+    fn target_descriptions_round_trip() {
+        let path = temp_config_path("description");
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(test_config());
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader
+            .config_mut()
+            .unwrap()
+            .targets
+            .as_mut()
+            .unwrap()[0]
+            .description = Some("decommission after Q3".to_owned());
+        config_reader.write().unwrap();
+
+        let config = ConfigReader::new(path.to_str()).read().unwrap();
+        remove_temp_config(&path);
+        let target = config.iter_targets().next().unwrap();
+        assert_eq!(target.description.as_deref(), Some("decommission after Q3"));
+        assert_eq!(
+            target.to_string(),
+            "web: www.test.de (decommission after Q3)"
+        );
+        assert_eq!(
+            TargetConfig {
+                port: Some(2222),
+                description: None,
+                tags: None,
+                ..target.clone()
+            }
+            .to_string(),
+            "web: www.test.de:2222"
+        );
+
+        let mut broken = config.clone();
+        broken.targets.as_mut().unwrap()[0].host = "www test.de".to_owned();
+        assert_eq!(
+            broken.validate()[0].message,
+            "targets: Invalid host for target web: \"www test.de\" (decommission after Q3)"
+        );
+    }
+
+    #[test]
+    fn display_name_falls_back_to_short_fingerprint() {
+        let named = test_config().me;
+        assert_eq!(named.display_name(), "test@test.de");
+
+        let unnamed = UserConfig {
+            name: " ".to_owned(),
+            fingerprint: "0e2d f37c e37b 47f1 b8c4 ec8c 1e2b c9be 7f3a d9a1".to_owned(),
+            ..named.clone()
+        };
+        assert_eq!(unnamed.display_name(), "7F3AD9A1");
+
+        let short = UserConfig {
+            name: String::new(),
+            fingerprint: "abc".to_owned(),
+            ..named
+        };
+        assert_eq!(short.display_name(), "ABC");
+    }
+
+    #[test]
+    fn config_types_dedup_in_hash_sets() {
+        let web = TargetConfig {
+            id: "web".to_owned(),
+            host: "www.test.de".to_owned(),
+            port: None,
+            format: None,
+            description: None,
+            tags: None,
+        };
+        let targets: HashSet<TargetConfig> = vec![
+            web.clone(),
+            web.clone(),
+            TargetConfig {
+                port: Some(8080),
+                ..web
+            },
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(targets.len(), 2);
+
+        let users: HashSet<UserConfig> = vec![test_config().me, test_config().me]
+            .into_iter()
+            .collect();
+        assert_eq!(users.len(), 1);
+    }
+
+    #[test]
+    fn iterates_config_targets() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        assert_eq!(
+            config_reader.config_ref().unwrap().iter_targets().count(),
+            0
+        );
+
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+
+        let mut ids = Vec::new();
+        for target in config_reader.config_ref().unwrap() {
+            ids.push(target.id.as_str());
+        }
+        assert_eq!(ids, vec!["web", "db"]);
+    }
+
+    #[test]
+    fn required_formats_cover_all_targets() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(test_config());
+        assert_eq!(
+            config_reader.config_ref().unwrap().required_formats(),
+            vec![EncryptionFormat::GPG_KEY].into_iter().collect()
+        );
+
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.add_target("db.test.de", "db").unwrap();
+        config_reader
+            .config_mut()
+            .unwrap()
+            .targets
+            .as_mut()
+            .unwrap()[1]
+            .format = Some("PLAINTEXT".to_owned());
+
+        let formats: Vec<EncryptionFormat> = config_reader
+            .config_ref()
+            .unwrap()
+            .required_formats()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            formats,
+            vec![EncryptionFormat::GPG_KEY, EncryptionFormat::PLAINTEXT]
+        );
+    }
+
+    #[test]
+    fn set_path_expands_home() {
+        let home = dirs::home_dir().unwrap();
+        let mut config_reader = ConfigReader::new(Some("./culper.toml"));
+        assert_eq!(config_reader.path(), Some(Path::new("./culper.toml")));
+
+        config_reader.set_path("~/culper/config.toml");
+        assert_eq!(
+            config_reader.path(),
+            Some(home.join("culper/config.toml").as_path())
+        );
+        assert_eq!(
+            ConfigReader::new(Some("~/culper.toml")).path(),
+            Some(home.join("culper.toml").as_path())
+        );
+    }
+
+    #[test]
+    fn validate_and_read_accepts_clean_config() {
+        let path = temp_config_path("validate-clean");
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(test_config());
+        config_reader.add_target("www.test.de", "web").unwrap();
+        config_reader.add_target("www.test.de", "web-old").unwrap();
+        config_reader.write().unwrap();
+
+        let config = ConfigReader::new(path.to_str())
+            .validate_and_read()
+            .unwrap();
+        remove_temp_config(&path);
+        assert_eq!(
+            config.validate(),
+            vec![ValidationIssue {
+                severity: Severity::Warning,
+                message: "targets: web, web-old share host www.test.de".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn validate_and_read_rejects_duplicate_ids() {
+        let path = temp_config_path("validate-duplicate");
+        let mut config = test_config();
+        let target = TargetConfig {
+            id: "web".to_owned(),
+            host: "www.test.de".to_owned(),
+            port: None,
+            format: None,
+            description: None,
+            tags: None,
+        };
+        config.targets = Some(vec![target.clone(), target]);
+        ConfigReader::new(path.to_str())
+            .update(config)
+            .write()
+            .unwrap();
+
+        let error = ConfigReader::new(path.to_str())
+            .validate_and_read()
+            .err()
+            .unwrap();
+        remove_temp_config(&path);
+        assert_eq!(
+            error.to_string(),
+            "Invalid config: targets: duplicate id web"
+        );
+    }
+
+    #[test]
+    fn list_accessors_default_to_empty() {
+        let mut config_reader = ConfigReader::new(None);
+        assert!(config_reader.targets().is_empty());
+        assert!(config_reader.owners().is_empty());
+
+        config_reader.update(test_config());
+        assert!(config_reader.admins().is_empty());
+
+        let mut config = test_config();
+        config.owners = Some(vec![config.me.clone()]);
+        config.admins = Some(vec![config.me.clone()]);
+        config_reader.update(config);
+        config_reader.add_target("www.test.de", "web").unwrap();
+
+        assert_eq!(config_reader.targets()[0].host, "www.test.de");
+        assert_eq!(config_reader.owners()[0].name, "test@test.de");
+        assert_eq!(config_reader.admins()[0].name, "test@test.de");
+    }
+
+    pub(crate) fn test_config() -> CulperConfig {
+        CulperConfig {
+            me: UserConfig {
+                name: "test@test.de".to_owned(),
+                fingerprint: "12345678".to_owned(),
+                added_at: None,
+                expires_at: None,
+            },
+            targets: None,
+            owners: None,
+            admins: None,
+            secrets: None,
+            secrets_file: None,
+        }
+    }
+
+    pub(crate) fn temp_config_path(name: &str) -> PathBuf {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!("culper-lib-{}-{}.toml", name, ::std::process::id()));
+        path
+    }
 
-        assert_eq!(contents, ::toml::to_string(&config_reader.config).unwrap())
+    /// Removes a temp config along with the `.lock` file writes leave next to it.
+    pub(crate) fn remove_temp_config(path: &Path) {
+        fs::remove_file(path).unwrap();
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let _ = fs::remove_file(lock_path);
     }
 }
@@ -0,0 +1,79 @@
+use super::ConfigReader;
+use failure::Error;
+
+impl ConfigReader {
+    /// Fetches a read-only config over HTTP(S). The returned reader has no path,
+    /// so `write` on it fails.
+    pub fn from_url(url: &str) -> Result<ConfigReader, Error> {
+        let response = match ureq::get(url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, _)) => {
+                return Err(format_err!(
+                    "Fetching config from {} failed with status {}",
+                    url,
+                    status
+                ))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if response.status() != 200 {
+            return Err(format_err!(
+                "Fetching config from {} failed with status {}",
+                url,
+                response.status()
+            ));
+        }
+
+        ConfigReader::from_reader(response.into_reader())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::prelude::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn serve_once(status_line: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status_line,
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        format!("http://{}/culper.toml", address)
+    }
+
+    #[test]
+    fn can_read_config_from_url() {
+        let url = serve_once(
+            "200 OK",
+            "[me]\nname = \"test@test.de\"\nfingerprint = \"12345678\"\n",
+        );
+        let config_reader = ConfigReader::from_url(&url).unwrap();
+
+        assert_eq!(
+            config_reader.config.as_ref().unwrap().me.name,
+            "test@test.de"
+        );
+        assert!(config_reader.write().is_err());
+    }
+
+    #[test]
+    fn reports_http_status_on_failure() {
+        let url = serve_once("404 Not Found", "");
+        let error = ConfigReader::from_url(&url).err().unwrap();
+
+        assert!(error.to_string().ends_with("failed with status 404"));
+    }
+}
@@ -0,0 +1,70 @@
+use super::{CulperConfig, RENAMED_KEYS};
+use serde_json::{json, Value};
+
+/// JSON Schema for the config file, for editor completion and validation.
+/// Old key names that are still read, like `me`, are listed as deprecated.
+pub fn json_schema() -> serde_json::Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(CulperConfig))
+        .expect("Failed converting schema to JSON.");
+    for (old_key, new_key) in RENAMED_KEYS {
+        add_deprecated_alias(&mut schema, old_key, new_key);
+    }
+    schema
+}
+
+// Lists `old_key` next to `new_key`, and requires either of them where
+// `new_key` was required.
+fn add_deprecated_alias(schema: &mut Value, old_key: &str, new_key: &str) {
+    let property = match schema["properties"].get(new_key) {
+        Some(property) => property.clone(),
+        None => return,
+    };
+    // Wrapped, since keywords next to a `$ref` are ignored.
+    schema["properties"][old_key] = json!({
+        "allOf": [property],
+        "deprecated": true,
+        "description": format!("Old name of `{}`, still read.", new_key),
+    });
+
+    let required = match schema.get_mut("required").and_then(Value::as_array_mut) {
+        Some(required) => required,
+        None => return,
+    };
+    if let Some(index) = required.iter().position(|key| key == new_key) {
+        required.remove(index);
+        schema["oneOf"] = json!([{ "required": [new_key] }, { "required": [old_key] }]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_describes_config_fields() {
+        let schema = json_schema();
+
+        assert!(schema["properties"]["identity"].is_object());
+        assert!(schema["properties"]["targets"].is_object());
+        assert!(schema["definitions"]["UserConfig"]["properties"]["fingerprint"].is_object());
+    }
+
+    #[test]
+    fn schema_accepts_the_deprecated_me_table() {
+        let schema = json_schema();
+
+        assert_eq!(
+            schema["properties"]["me"]["allOf"][0],
+            schema["properties"]["identity"]
+        );
+        assert_eq!(schema["properties"]["me"]["deprecated"], true);
+        assert_eq!(
+            schema["oneOf"],
+            json!([{ "required": ["identity"] }, { "required": ["me"] }])
+        );
+        assert!(!schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("identity")));
+    }
+}
@@ -0,0 +1,373 @@
+use super::{expand_home, ConfigReader, CulperConfig};
+use crate::vault::{parse, strip_tokens, SealedVault, UnsealedVault, VaultHandler};
+use failure::{Error, ResultExt};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use toml::Value;
+
+impl ConfigReader {
+    /// Re-encrypts every sealed value in the config from `old` to `new` and
+    /// returns how many were resealed. Other strings are left alone.
+    pub fn reseal_secrets(
+        &mut self,
+        old: &dyn VaultHandler,
+        new: &dyn VaultHandler,
+    ) -> Result<usize, Error> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return Err(format_err!("Config is not set.")),
+        };
+
+        let mut count = 0;
+        let mut value = Value::try_from(config)?;
+        walk_strings_mut(&mut value, &mut |string| {
+            if let Ok(vault) = parse(string) {
+                *string = new.encrypt(old.decrypt(vault)?)?.to_string();
+                count += 1;
+            }
+            Ok(())
+        })?;
+
+        let resealed: CulperConfig = value.try_into()?;
+        self.config = Some(resealed);
+        Ok(count)
+    }
+
+    /// Looks up a sealed value in the config's `secrets_file`, a TOML table of
+    /// tokens by name.
+    pub fn secret(&self, name: &str) -> Result<SealedVault, Error> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return Err(format_err!("Config is not set.")),
+        };
+        let file = match &config.secrets_file {
+            Some(file) => expand_home(PathBuf::from(file)),
+            None => return Err(format_err!("The config has no secrets_file.")),
+        };
+        let file = match self.path.as_ref().and_then(|path| path.parent()) {
+            Some(dir) if file.is_relative() => dir.join(file),
+            _ => file,
+        };
+        if !file.exists() {
+            return Err(format_err!("Secrets file {} not found.", file.display()));
+        }
+
+        let raw_toml = fs::read_to_string(&file)
+            .context(format!("Could not read secrets file {}", file.display()))?;
+        let secrets: BTreeMap<String, String> = toml::from_str(&raw_toml)
+            .context(format!("Invalid secrets file {}", file.display()))?;
+        match secrets.get(name) {
+            Some(token) => parse(token),
+            None => Err(format_err!(
+                "No secret named {} in {}.",
+                name,
+                file.display()
+            )),
+        }
+    }
+
+    /// The normalized fingerprints in the config that `handler` cannot encrypt
+    /// to. An empty list means sealing for everyone will work.
+    pub fn check_recipients(&self, handler: &dyn VaultHandler) -> Result<Vec<String>, Error> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return Err(format_err!("Config is not set.")),
+        };
+        Ok(config
+            .all_fingerprints()
+            .into_iter()
+            .filter(|fingerprint| !handler.can_encrypt_to(fingerprint))
+            .collect())
+    }
+}
+
+const REDACTED_FINGERPRINT: &str = "<redacted>";
+const REDACTED_TOKEN: &str = "<redacted token>";
+
+impl CulperConfig {
+    /// A copy that is safe to share: fingerprints are masked, sealed values
+    /// dropped from `secrets` and tokens anywhere else replaced. Names, hosts
+    /// and plain values are kept.
+    pub fn redacted(&self) -> CulperConfig {
+        let mut redacted = self.clone();
+        let users = ::std::iter::once(&mut redacted.me)
+            .chain(redacted.owners.iter_mut().flatten())
+            .chain(redacted.admins.iter_mut().flatten());
+        for user in users {
+            user.fingerprint = REDACTED_FINGERPRINT.to_owned();
+        }
+        if let Some(secrets) = &mut redacted.secrets {
+            secrets.retain(|_, value| parse(value).is_err());
+        }
+
+        let mut value = Value::try_from(&redacted).expect("config converts to TOML");
+        walk_strings_mut(&mut value, &mut |string| {
+            *string = strip_tokens(string, |_| REDACTED_TOKEN.to_owned());
+            Ok(())
+        })
+        .expect("redacting strings can't fail");
+        value.try_into().expect("redacted config converts back")
+    }
+
+    /// Replaces the string at the dotted `path` (e.g. `secrets.token` or
+    /// `targets.0.host`) with a token sealed by `handler`.
+    pub fn seal_field(&mut self, path: &str, handler: &dyn VaultHandler) -> Result<(), Error> {
+        self.map_field(path, &mut |string| {
+            let unsealed = UnsealedVault::new(string.clone(), handler.format());
+            *string = handler.encrypt(unsealed)?.to_string();
+            Ok(())
+        })
+    }
+
+    /// Replaces the token at the dotted `path` with its plaintext.
+    pub fn unseal_field(&mut self, path: &str, handler: &dyn VaultHandler) -> Result<(), Error> {
+        self.map_field(path, &mut |string| {
+            let unsealed = handler.decrypt(parse(string)?)?;
+            *string = unsealed.plain_secret.to_string();
+            Ok(())
+        })
+    }
+
+    fn map_field(
+        &mut self,
+        path: &str,
+        f: &mut dyn FnMut(&mut String) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut value = Value::try_from(&*self)?;
+        let mut field = &mut value;
+        for key in path.split('.') {
+            field = match field {
+                Value::Table(table) => table.get_mut(key),
+                Value::Array(values) => key
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(move |index| values.get_mut(index)),
+                _ => None,
+            }
+            .ok_or_else(|| format_err!("No config value at {}.", path))?;
+        }
+        match field {
+            Value::String(string) => f(string)?,
+            _ => return Err(format_err!("Config value at {} is not a string.", path)),
+        }
+        *self = value.try_into()?;
+        Ok(())
+    }
+}
+
+fn walk_strings_mut(
+    value: &mut Value,
+    f: &mut dyn FnMut(&mut String) -> Result<(), Error>,
+) -> Result<(), Error> {
+    match value {
+        Value::String(string) => f(string)?,
+        Value::Array(values) => {
+            for value in values {
+                walk_strings_mut(value, f)?;
+            }
+        }
+        Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                walk_strings_mut(value, f)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::{remove_temp_config, test_config};
+    use crate::config::UserConfig;
+    use crate::vault::{
+        EncryptionFormat, FnVaultHandler, PlaintextVaultHandler, SealedVault, UnsealedVault,
+    };
+    use std::collections::BTreeMap;
+
+    struct Keyring(&'static [&'static str]);
+
+    impl VaultHandler for Keyring {
+        fn format(&self) -> EncryptionFormat {
+            EncryptionFormat::GPG_KEY
+        }
+
+        fn encrypt(&self, _: UnsealedVault) -> Result<SealedVault, Error> {
+            Err(format_err!("not needed"))
+        }
+
+        fn decrypt(&self, _: SealedVault) -> Result<UnsealedVault, Error> {
+            Err(format_err!("not needed"))
+        }
+
+        fn can_encrypt_to(&self, fingerprint: &str) -> bool {
+            self.0.contains(&fingerprint)
+        }
+    }
+
+    #[test]
+    fn resolves_secrets_from_sidecar_file() {
+        let mut dir = ::std::env::temp_dir();
+        dir.push(format!("culper-lib-sidecar-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db = SealedVault::new(b"hunter2".to_vec(), EncryptionFormat::PLAINTEXT);
+        let api = SealedVault::new(b"s3cr3t".to_vec(), EncryptionFormat::GPG_KEY);
+        fs::write(
+            dir.join("secrets.culper"),
+            format!("db = \"{}\"\napi = \"{}\"\n", db, api),
+        )
+        .unwrap();
+
+        let config_path = dir.join("culper.toml");
+        let mut config_reader = ConfigReader::new(config_path.to_str());
+        config_reader.update(CulperConfig {
+            secrets_file: Some("secrets.culper".to_owned()),
+            ..test_config()
+        });
+
+        assert_eq!(config_reader.secret("db").unwrap(), db);
+        let error = config_reader.secret("missing").err().unwrap().to_string();
+        assert!(error.starts_with("No secret named missing in "));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_unreachable_recipients() {
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(CulperConfig {
+            owners: Some(vec![UserConfig {
+                name: "gone@test.de".to_owned(),
+                fingerprint: "abcdef01".to_owned(),
+                added_at: None,
+                expires_at: None,
+            }]),
+            ..test_config()
+        });
+
+        assert_eq!(
+            config_reader
+                .check_recipients(&Keyring(&["12345678"]))
+                .unwrap(),
+            vec!["ABCDEF01"]
+        );
+        assert!(config_reader
+            .check_recipients(&PlaintextVaultHandler)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn redaction_keeps_hosts_and_drops_secrets() {
+        let mut secrets = BTreeMap::new();
+        let sealed = SealedVault::new(b"hunter2".to_vec(), EncryptionFormat::PLAINTEXT);
+        secrets.insert("db".to_owned(), sealed.to_string());
+        secrets.insert("note".to_owned(), "readable".to_owned());
+
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(CulperConfig {
+            secrets: Some(secrets),
+            ..test_config()
+        });
+        config_reader.add_target("www.test.de", "web").unwrap();
+
+        let config = config_reader.config_mut().unwrap();
+        let target = &mut config.targets.as_mut().unwrap()[0];
+        target.description = Some(format!("old password {}", sealed));
+        target.tags = Some(vec![sealed.to_string()]);
+
+        let rendered = toml::to_string(&config_reader.config.unwrap().redacted()).unwrap();
+        assert!(rendered.contains("www.test.de"));
+        assert!(rendered.contains("test@test.de"));
+        assert!(rendered.contains("readable"));
+        assert!(!rendered.contains("12345678"));
+        assert!(!rendered.contains(&sealed.to_string()));
+        assert!(rendered.contains("old password <redacted token>"));
+        assert!(toml::from_str::<CulperConfig>(&rendered).is_ok());
+    }
+
+    #[test]
+    fn seals_and_unseals_single_fields() {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!(
+            "culper-lib-seal-field-{}.toml",
+            ::std::process::id()
+        ));
+        let mut secrets = BTreeMap::new();
+        secrets.insert("token".to_owned(), "hunter2".to_owned());
+        secrets.insert("note".to_owned(), "readable".to_owned());
+
+        let mut config = CulperConfig {
+            secrets: Some(secrets),
+            ..test_config()
+        };
+        config
+            .seal_field("secrets.token", &PlaintextVaultHandler)
+            .unwrap();
+        assert_eq!(
+            config
+                .seal_field("secrets.missing", &PlaintextVaultHandler)
+                .err()
+                .unwrap()
+                .to_string(),
+            "No config value at secrets.missing."
+        );
+
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.update(config).write().unwrap();
+        let mut config_reader = ConfigReader::new(path.to_str());
+        config_reader.read().unwrap();
+        let mut config = config_reader.config.unwrap();
+        assert!(parse(&config.secrets.as_ref().unwrap()["token"]).is_ok());
+        assert_eq!(config.secrets.as_ref().unwrap()["note"], "readable");
+
+        config
+            .unseal_field("secrets.token", &PlaintextVaultHandler)
+            .unwrap();
+        assert_eq!(config.secrets.unwrap()["token"], "hunter2");
+        remove_temp_config(&path);
+    }
+
+    #[test]
+    fn reseals_every_sealed_value() {
+        let mut secrets = BTreeMap::new();
+        for (name, secret) in [("db", "hunter2"), ("api", "s3cr3t")].iter() {
+            let sealed = SealedVault::new(secret.as_bytes().to_vec(), EncryptionFormat::PLAINTEXT);
+            secrets.insert(name.to_string(), sealed.to_string());
+        }
+        secrets.insert("note".to_owned(), "not a secret".to_owned());
+
+        let mut config_reader = ConfigReader::new(None);
+        config_reader.update(CulperConfig {
+            secrets: Some(secrets),
+            ..test_config()
+        });
+
+        let reversing = FnVaultHandler::new(
+            |u: UnsealedVault| {
+                let reversed: String = u.plain_secret.chars().rev().collect();
+                Ok(SealedVault::new(
+                    reversed.into_bytes(),
+                    EncryptionFormat::GPG_KEY,
+                ))
+            },
+            |_| Err(format_err!("not needed")),
+        );
+        let count = config_reader
+            .reseal_secrets(&PlaintextVaultHandler, &reversing)
+            .unwrap();
+
+        let secrets = config_reader.config.unwrap().secrets.unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            parse(&secrets["db"]).unwrap(),
+            SealedVault::new(b"2retnuh".to_vec(), EncryptionFormat::GPG_KEY)
+        );
+        assert_eq!(
+            parse(&secrets["api"]).unwrap(),
+            SealedVault::new(b"t3rc3s".to_vec(), EncryptionFormat::GPG_KEY)
+        );
+        assert_eq!(secrets["note"], "not a secret");
+    }
+}
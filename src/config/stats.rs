@@ -0,0 +1,102 @@
+use super::CulperConfig;
+use crate::vault::EncryptionFormat;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Counts over a config, e.g. for dashboards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigStats {
+    pub targets: usize,
+    pub owners: usize,
+    pub admins: usize,
+    pub formats: BTreeSet<EncryptionFormat>,
+}
+
+impl fmt::Display for ConfigStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let formats: Vec<&str> = self
+            .formats
+            .iter()
+            .map(EncryptionFormat::as_static_str)
+            .collect();
+        write!(
+            f,
+            "{} targets, {} owners, {} admins, formats: {}",
+            self.targets,
+            self.owners,
+            self.admins,
+            formats.join(", ")
+        )
+    }
+}
+
+impl CulperConfig {
+    /// How many targets, owners and admins there are, and the formats
+    /// `required_formats` reports.
+    pub fn stats(&self) -> ConfigStats {
+        ConfigStats {
+            targets: self.targets.as_ref().map_or(0, Vec::len),
+            owners: self.owners.as_ref().map_or(0, Vec::len),
+            admins: self.admins.as_ref().map_or(0, Vec::len),
+            formats: self.required_formats(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::test_config;
+    use crate::config::{TargetConfig, UserConfig};
+
+    fn user(fingerprint: &str) -> UserConfig {
+        UserConfig {
+            name: String::new(),
+            fingerprint: fingerprint.to_owned(),
+            added_at: None,
+            expires_at: None,
+        }
+    }
+
+    fn target(id: &str, format: Option<&str>) -> TargetConfig {
+        TargetConfig {
+            id: id.to_owned(),
+            host: format!("{}.test.de", id),
+            port: None,
+            format: format.map(str::to_owned),
+            description: None,
+            tags: None,
+        }
+    }
+
+    #[test]
+    fn counts_entries_and_formats() {
+        let config = CulperConfig {
+            targets: Some(vec![
+                target("web", Some("AES_GCM")),
+                target("db", None),
+                target("cache", Some("AES_GCM")),
+            ]),
+            owners: Some(vec![user("AAAA"), user("BBBB")]),
+            me: user("AAAA"),
+            ..test_config()
+        };
+
+        let stats = config.stats();
+        assert_eq!(
+            stats,
+            ConfigStats {
+                targets: 3,
+                owners: 2,
+                admins: 0,
+                formats: vec![EncryptionFormat::GPG_KEY, EncryptionFormat::AES_GCM]
+                    .into_iter()
+                    .collect(),
+            }
+        );
+        assert_eq!(
+            stats.to_string(),
+            "3 targets, 2 owners, 0 admins, formats: GPG_KEY, AES_GCM"
+        );
+    }
+}
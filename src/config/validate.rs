@@ -0,0 +1,100 @@
+use super::{normalize_fingerprint, CulperConfig, UserConfig};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn error(message: String) -> ValidationIssue {
+        ValidationIssue {
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    fn warning(message: String) -> ValidationIssue {
+        ValidationIssue {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl CulperConfig {
+    /// Everything wrong with the config. Errors make it unusable, warnings are
+    /// likely mistakes that don't stop culper from working.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        if let Err(err) = self.me.validate() {
+            issues.push(ValidationIssue::error(format!("me: {}", err)));
+        }
+        validate_users("owners", &self.owners, &mut issues);
+        validate_users("admins", &self.admins, &mut issues);
+
+        let mut ids = BTreeSet::new();
+        let mut ids_by_host: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for target in self.targets.iter().flatten() {
+            if let Err(err) = target.validate() {
+                let message = match &target.description {
+                    Some(description) => format!("targets: {} ({})", err, description),
+                    None => format!("targets: {}", err),
+                };
+                issues.push(ValidationIssue::error(message));
+            }
+            if !ids.insert(&target.id) {
+                issues.push(ValidationIssue::error(format!(
+                    "targets: duplicate id {}",
+                    target.id
+                )));
+            }
+            ids_by_host
+                .entry(&target.host)
+                .or_default()
+                .push(&target.id);
+        }
+        for (host, ids) in ids_by_host {
+            if ids.len() > 1 {
+                issues.push(ValidationIssue::warning(format!(
+                    "targets: {} share host {}",
+                    ids.join(", "),
+                    host
+                )));
+            }
+        }
+        issues
+    }
+}
+
+fn validate_users(list: &str, users: &Option<Vec<UserConfig>>, issues: &mut Vec<ValidationIssue>) {
+    let mut fingerprints = BTreeSet::new();
+    for user in users.iter().flatten() {
+        if let Err(err) = user.validate() {
+            issues.push(ValidationIssue::error(format!("{}: {}", list, err)));
+        }
+        if !fingerprints.insert(normalize_fingerprint(&user.fingerprint)) {
+            issues.push(ValidationIssue::warning(format!(
+                "{}: {} is listed more than once",
+                list, user.fingerprint
+            )));
+        }
+    }
+}
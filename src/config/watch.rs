@@ -0,0 +1,128 @@
+use super::{ConfigReader, CulperConfig};
+use failure::Error;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Writes closer together than this are reported once.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Keeps a `ConfigReader::watch` running until dropped.
+pub struct WatchHandle {
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        // Dropping the watcher closes the channel, which ends the thread.
+        self.watcher.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl ConfigReader {
+    /// Calls `on_change` with the freshly read config whenever the file
+    /// changes, once per burst of writes. Changes that don't parse, e.g. a
+    /// half-saved file, are skipped.
+    pub fn watch<F>(&self, on_change: F) -> Result<WatchHandle, Error>
+    where
+        F: Fn(CulperConfig) + Send + 'static,
+    {
+        let path = self.require_path()?.to_path_buf();
+        let file_name: OsString = match path.file_name() {
+            Some(name) => name.to_owned(),
+            None => return Err(format_err!("{} is not a file.", path.display())),
+        };
+        // Watch the directory, since editors and `write` replace the file.
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => ".".into(),
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<_>| {
+            if let Ok(event) = event {
+                let _ = sender.send(event);
+            }
+        })
+        .map_err(|err| format_err!("Could not watch {}: {}", path.display(), err))?;
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .map_err(|err| format_err!("Could not watch {}: {}", path.display(), err))?;
+
+        let mut reader = ConfigReader::new(None);
+        reader.set_path(path);
+        reader.with_env_interpolation(self.interpolate_env);
+        let thread = thread::spawn(move || loop {
+            let event: notify::Event = match receiver.recv() {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            if !event
+                .paths
+                .iter()
+                .any(|changed| changed.file_name() == Some(file_name.as_os_str()))
+            {
+                continue;
+            }
+            loop {
+                match receiver.recv_timeout(DEBOUNCE) {
+                    Ok(_) => continue,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if let Ok(config) = reader.read() {
+                on_change(config);
+            }
+        });
+
+        Ok(WatchHandle {
+            watcher: Some(watcher),
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reports_edits_to_the_file() {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!("culper-lib-watch-{}", ::std::process::id()));
+        fs::create_dir_all(&path).unwrap();
+        path.push("culper.toml");
+        fs::write(
+            &path,
+            "[identity]\nname = \"before@test.de\"\nfingerprint = \"12345678\"\n",
+        )
+        .unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let config_reader = ConfigReader::new(path.to_str());
+        let handle = config_reader
+            .watch(move |config| {
+                let _ = sender.send(config.me.name);
+            })
+            .unwrap();
+
+        fs::write(
+            &path,
+            "[identity]\nname = \"after@test.de\"\nfingerprint = \"12345678\"\n",
+        )
+        .unwrap();
+        let name = receiver.recv_timeout(Duration::from_secs(10));
+        drop(handle);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(name.unwrap(), "after@test.de");
+    }
+}
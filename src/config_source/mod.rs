@@ -0,0 +1,123 @@
+use crate::vault::{parse, VaultHandler};
+use config_rs::{ConfigError, Map, Source, Value, ValueKind};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use toml;
+
+/// A `config` crate source that reads a TOML file and decrypts every string
+/// value that is a Culper token. Other values pass through unchanged.
+#[derive(Clone)]
+pub struct CulperSource {
+    path: PathBuf,
+    handler: Arc<dyn VaultHandler + Send + Sync>,
+}
+
+impl CulperSource {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        handler: Arc<dyn VaultHandler + Send + Sync>,
+    ) -> CulperSource {
+        CulperSource {
+            path: path.into(),
+            handler,
+        }
+    }
+
+    fn convert(&self, value: toml::Value, origin: &String) -> Result<Value, ConfigError> {
+        let kind = match value {
+            toml::Value::String(string) => match parse(&string) {
+                Ok(vault) => match self.handler.decrypt(vault) {
+                    Ok(unsealed) => ValueKind::String(unsealed.plain_secret.to_string()),
+                    Err(err) => {
+                        return Err(ConfigError::Message(format!(
+                            "Could not decrypt value in {}: {}",
+                            origin, err
+                        )))
+                    }
+                },
+                Err(_) => ValueKind::String(string),
+            },
+            toml::Value::Integer(integer) => ValueKind::I64(integer),
+            toml::Value::Float(float) => ValueKind::Float(float),
+            toml::Value::Boolean(boolean) => ValueKind::Boolean(boolean),
+            toml::Value::Datetime(datetime) => ValueKind::String(datetime.to_string()),
+            toml::Value::Array(values) => ValueKind::Array(
+                values
+                    .into_iter()
+                    .map(|value| self.convert(value, origin))
+                    .collect::<Result<_, _>>()?,
+            ),
+            toml::Value::Table(table) => ValueKind::Table(self.convert_table(table, origin)?),
+        };
+        Ok(Value::new(Some(origin), kind))
+    }
+
+    fn convert_table(
+        &self,
+        table: toml::value::Table,
+        origin: &String,
+    ) -> Result<Map<String, Value>, ConfigError> {
+        let mut converted = Map::new();
+        for (key, value) in table {
+            converted.insert(key, self.convert(value, origin)?);
+        }
+        Ok(converted)
+    }
+}
+
+impl fmt::Debug for CulperSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CulperSource")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl Source for CulperSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        let origin = self.path.to_string_lossy().into_owned();
+        let raw = fs::read_to_string(&self.path).map_err(|err| ConfigError::Foreign(err.into()))?;
+        match raw.parse() {
+            Ok(toml::Value::Table(table)) => self.convert_table(table, &origin),
+            Ok(_) => Err(ConfigError::Message(format!(
+                "{} does not contain a TOML table",
+                origin
+            ))),
+            Err(err) => Err(ConfigError::Foreign(Box::new(err))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::{EncryptionFormat, PlaintextVaultHandler, SealedVault};
+    use config_rs::Config;
+
+    #[test]
+    fn decrypts_tokens_while_layering() {
+        let token = SealedVault::new(b"hunter2".to_vec(), EncryptionFormat::PLAINTEXT).to_string();
+        let mut path = std::env::temp_dir();
+        path.push(format!("culper-lib-source-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            format!("user = \"admin\"\n\n[db]\npassword = \"{}\"\n", token),
+        )
+        .unwrap();
+
+        let config = Config::builder()
+            .add_source(CulperSource::new(&path, Arc::new(PlaintextVaultHandler)))
+            .build()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.get_string("user").unwrap(), "admin");
+        assert_eq!(config.get_string("db.password").unwrap(), "hunter2");
+    }
+}
@@ -4,4 +4,6 @@ extern crate failure;
 extern crate serde_derive;
 
 pub mod config;
+#[cfg(feature = "config-crate")]
+pub mod config_source;
 pub mod vault;
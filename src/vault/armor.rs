@@ -0,0 +1,88 @@
+use super::{EncryptionFormat, SealedVault};
+use base64::{decode, encode};
+use failure::{Error, ResultExt};
+
+const BEGIN: &str = "-----BEGIN CULPER VAULT-----";
+const END: &str = "-----END CULPER VAULT-----";
+const LINE_WIDTH: usize = 64;
+
+impl SealedVault {
+    /// A line-wrapped block that survives being pasted into mails or tickets.
+    pub fn to_armored(&self) -> String {
+        let payload = encode(&self.secret);
+        let mut armored = format!("{}\nFormat: {}\n\n", BEGIN, self.format);
+        for line in payload.as_bytes().chunks(LINE_WIDTH) {
+            armored.push_str(&String::from_utf8_lossy(line));
+            armored.push('\n');
+        }
+        armored.push_str(END);
+        armored
+    }
+
+    /// Parses the first armored block in `text`, ignoring anything around it.
+    pub fn from_armored(text: &str) -> Result<SealedVault, Error> {
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| *line != BEGIN)
+            .skip(1);
+
+        let mut format = None;
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            match line.find(':') {
+                Some(split) if line[..split].trim() == "Format" => {
+                    format = Some(EncryptionFormat::from_str(line[split + 1..].trim())?)
+                }
+                Some(_) => {}
+                None => return Err(format_err!("Malformed armor header: {}", line)),
+            }
+        }
+
+        let mut payload = String::new();
+        let mut terminated = false;
+        for line in lines {
+            if line == END {
+                terminated = true;
+                break;
+            }
+            payload.push_str(line);
+        }
+        if !terminated {
+            return Err(format_err!("No complete armored Culper vault found."));
+        }
+
+        Ok(SealedVault::new(
+            decode(&payload).context("Failed to decode base64 payload")?,
+            format.ok_or_else(|| format_err!("Armored vault has no Format header."))?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn armored_round_trips() {
+        let vault = SealedVault::new((0..200u8).collect(), EncryptionFormat::GPG_KEY);
+        let armored = vault.to_armored();
+
+        assert!(armored.starts_with("-----BEGIN CULPER VAULT-----\nFormat: GPG_KEY\n\n"));
+        assert!(armored.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert_eq!(SealedVault::from_armored(&armored).unwrap(), vault);
+    }
+
+    #[test]
+    fn from_armored_ignores_surrounding_text() {
+        let vault = SealedVault::new(b"the launch codes".to_vec(), EncryptionFormat::PLAINTEXT);
+        let mail = format!(
+            "Hi,\n\nhere is the secret you asked for:\n\n{}\n\nCheers\n",
+            vault.to_armored()
+        );
+
+        assert_eq!(SealedVault::from_armored(&mail).unwrap(), vault);
+    }
+}
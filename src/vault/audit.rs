@@ -0,0 +1,374 @@
+use super::{EncryptionFormat, SealedVault, UnsealedVault, VaultHandler};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOperation {
+    Encrypt,
+    Decrypt,
+    /// Re-encrypting a vault for other recipients, see `VaultHandler::rewrap`.
+    Rewrap,
+}
+
+/// One recorded operation. Only the ciphertext size is kept, never the secret.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub at: SystemTime,
+    pub operation: AuditOperation,
+    pub format: EncryptionFormat,
+    pub success: bool,
+    /// Size of the ciphertext read or written, 0 if encrypting failed. For
+    /// rewraps, the new ciphertext, or the old one if rewrapping failed.
+    pub ciphertext_size: usize,
+}
+
+/// Where `AuditingVaultHandler` records its entries.
+pub trait AuditSink {
+    fn record(&self, entry: AuditEntry);
+}
+
+impl AuditSink for Mutex<Vec<AuditEntry>> {
+    fn record(&self, entry: AuditEntry) {
+        self.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(entry);
+    }
+}
+
+impl<F: Fn(AuditEntry)> AuditSink for F {
+    fn record(&self, entry: AuditEntry) {
+        self(entry)
+    }
+}
+
+impl<S: AuditSink + ?Sized> AuditSink for Arc<S> {
+    fn record(&self, entry: AuditEntry) {
+        (**self).record(entry)
+    }
+}
+
+/// Writes one line per entry, e.g. to an append-only log file. Write errors
+/// are ignored so a full disk can't turn into failed decrypts.
+pub struct WriteAuditSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write> WriteAuditSink<W> {
+    pub fn new(writer: W) -> WriteAuditSink<W> {
+        WriteAuditSink {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<W: Write> AuditSink for WriteAuditSink<W> {
+    fn record(&self, entry: AuditEntry) {
+        let at = entry
+            .at
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = writeln!(
+            writer,
+            "{} {:?} {} {} {}",
+            at,
+            entry.operation,
+            entry.format,
+            if entry.success { "ok" } else { "failed" },
+            entry.ciphertext_size
+        );
+    }
+}
+
+/// Delegates to `inner` and records every encrypt and decrypt in `sink`.
+pub struct AuditingVaultHandler<H, S> {
+    inner: H,
+    sink: S,
+}
+
+impl<H: VaultHandler, S: AuditSink> AuditingVaultHandler<H, S> {
+    pub fn new(inner: H, sink: S) -> AuditingVaultHandler<H, S> {
+        AuditingVaultHandler { inner, sink }
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+
+    fn record(
+        &self,
+        operation: AuditOperation,
+        format: EncryptionFormat,
+        success: bool,
+        ciphertext_size: usize,
+    ) {
+        self.sink.record(AuditEntry {
+            at: SystemTime::now(),
+            operation,
+            format,
+            success,
+            ciphertext_size,
+        });
+    }
+
+    // Successes are logged with the format actually produced, failures with
+    // the one the handler advertises.
+    fn record_encrypt(&self, sealed: Option<&SealedVault>) {
+        match sealed {
+            Some(sealed) => self.record(
+                AuditOperation::Encrypt,
+                sealed.format.clone(),
+                true,
+                sealed.secret.len(),
+            ),
+            None => self.record(AuditOperation::Encrypt, self.inner.format(), false, 0),
+        }
+    }
+}
+
+impl<H: VaultHandler, S: AuditSink> VaultHandler for AuditingVaultHandler<H, S> {
+    fn format(&self) -> EncryptionFormat {
+        self.inner.format()
+    }
+
+    fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, failure::Error> {
+        let result = self.inner.encrypt(u);
+        self.record_encrypt(result.as_ref().ok());
+        result
+    }
+
+    fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, failure::Error> {
+        let format = s.format.clone();
+        let ciphertext_size = s.secret.len();
+        let result = self.inner.decrypt(s);
+        self.record(
+            AuditOperation::Decrypt,
+            format,
+            result.is_ok(),
+            ciphertext_size,
+        );
+        result
+    }
+
+    /// Forwards to the inner handler's batch and records an entry per item. A
+    /// failed batch is recorded as a failure for every item.
+    fn encrypt_many(&self, items: Vec<UnsealedVault>) -> Result<Vec<SealedVault>, failure::Error> {
+        let count = items.len();
+        let result = self.inner.encrypt_many(items);
+        match &result {
+            Ok(sealed) => sealed
+                .iter()
+                .for_each(|sealed| self.record_encrypt(Some(sealed))),
+            Err(_) => (0..count).for_each(|_| self.record_encrypt(None)),
+        }
+        result
+    }
+
+    /// See `encrypt_many`.
+    fn decrypt_many(&self, items: Vec<SealedVault>) -> Result<Vec<UnsealedVault>, failure::Error> {
+        let inputs: Vec<(EncryptionFormat, usize)> = items
+            .iter()
+            .map(|s| (s.format.clone(), s.secret.len()))
+            .collect();
+        let result = self.inner.decrypt_many(items);
+        for (format, ciphertext_size) in inputs {
+            self.record(
+                AuditOperation::Decrypt,
+                format,
+                result.is_ok(),
+                ciphertext_size,
+            );
+        }
+        result
+    }
+
+    fn can_encrypt_to(&self, fingerprint: &str) -> bool {
+        self.inner.can_encrypt_to(fingerprint)
+    }
+
+    fn encrypt_for(
+        &self,
+        u: UnsealedVault,
+        recipients: &[&str],
+    ) -> Result<SealedVault, failure::Error> {
+        let result = self.inner.encrypt_for(u, recipients);
+        self.record_encrypt(result.as_ref().ok());
+        result
+    }
+
+    fn rewrap(
+        &self,
+        s: SealedVault,
+        new_recipients: &[&str],
+    ) -> Result<SealedVault, failure::Error> {
+        let format = s.format.clone();
+        let ciphertext_size = s.secret.len();
+        let result = self.inner.rewrap(s, new_recipients);
+        match &result {
+            Ok(sealed) => self.record(
+                AuditOperation::Rewrap,
+                sealed.format.clone(),
+                true,
+                sealed.secret.len(),
+            ),
+            Err(_) => self.record(AuditOperation::Rewrap, format, false, ciphertext_size),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::PlaintextVaultHandler;
+
+    #[test]
+    fn records_decrypts_without_plaintext() {
+        let handler = AuditingVaultHandler::new(PlaintextVaultHandler, Mutex::new(Vec::new()));
+        let sealed = handler
+            .encrypt(UnsealedVault::new(
+                "hunter2".to_owned(),
+                EncryptionFormat::PLAINTEXT,
+            ))
+            .unwrap();
+        let unsealed = handler.decrypt(sealed).unwrap();
+        assert_eq!(unsealed.plain_secret.as_str(), "hunter2");
+        assert!(handler
+            .decrypt(SealedVault::new(vec![1, 2, 3], EncryptionFormat::GPG_KEY))
+            .is_err());
+
+        let entries = handler.sink().lock().unwrap();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.operation, entry.success, entry.ciphertext_size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (AuditOperation::Encrypt, true, 7),
+                (AuditOperation::Decrypt, true, 7),
+                (AuditOperation::Decrypt, false, 3),
+            ]
+        );
+        assert!(!format!("{:?}", entries).contains("hunter2"));
+    }
+
+    #[test]
+    fn records_the_format_actually_produced() {
+        let handler = AuditingVaultHandler::new(PlaintextVaultHandler, Mutex::new(Vec::new()));
+        handler
+            .encrypt(UnsealedVault::new(
+                "secret".to_owned(),
+                EncryptionFormat::GPG_KEY,
+            ))
+            .unwrap();
+
+        let entries = handler.sink().lock().unwrap();
+        assert_eq!(entries[0].format, EncryptionFormat::PLAINTEXT);
+    }
+
+    struct BatchOnly;
+
+    impl VaultHandler for BatchOnly {
+        fn format(&self) -> EncryptionFormat {
+            EncryptionFormat::PLAINTEXT
+        }
+
+        fn encrypt(&self, _u: UnsealedVault) -> Result<SealedVault, failure::Error> {
+            Err(format_err!("Only batches are supported."))
+        }
+
+        fn decrypt(&self, _s: SealedVault) -> Result<UnsealedVault, failure::Error> {
+            Err(format_err!("Only batches are supported."))
+        }
+
+        fn encrypt_many(
+            &self,
+            items: Vec<UnsealedVault>,
+        ) -> Result<Vec<SealedVault>, failure::Error> {
+            items
+                .into_iter()
+                .map(|u| PlaintextVaultHandler.encrypt(u))
+                .collect()
+        }
+
+        fn decrypt_many(
+            &self,
+            items: Vec<SealedVault>,
+        ) -> Result<Vec<UnsealedVault>, failure::Error> {
+            items
+                .into_iter()
+                .map(|s| PlaintextVaultHandler.decrypt(s))
+                .collect()
+        }
+
+        fn rewrap(
+            &self,
+            s: SealedVault,
+            _new_recipients: &[&str],
+        ) -> Result<SealedVault, failure::Error> {
+            Ok(s)
+        }
+    }
+
+    #[test]
+    fn forwards_batches_and_rewraps() {
+        let handler = AuditingVaultHandler::new(BatchOnly, Mutex::new(Vec::new()));
+        let sealed = handler
+            .encrypt_many(vec![
+                UnsealedVault::new("a".to_owned(), EncryptionFormat::PLAINTEXT),
+                UnsealedVault::new("bc".to_owned(), EncryptionFormat::PLAINTEXT),
+            ])
+            .unwrap();
+        let rewrapped = handler.rewrap(sealed[0].clone(), &["ABCD"]).unwrap();
+        let unsealed = handler
+            .decrypt_many(vec![rewrapped, sealed[1].clone()])
+            .unwrap();
+        assert_eq!(*unsealed[1].plain_secret, "bc");
+
+        let entries = handler.sink().lock().unwrap();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.operation, entry.success, entry.ciphertext_size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (AuditOperation::Encrypt, true, 1),
+                (AuditOperation::Encrypt, true, 2),
+                (AuditOperation::Rewrap, true, 1),
+                (AuditOperation::Decrypt, true, 1),
+                (AuditOperation::Decrypt, true, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_one_line_per_entry() {
+        let sink = Arc::new(WriteAuditSink::new(Vec::new()));
+        let handler = AuditingVaultHandler::new(PlaintextVaultHandler, sink.clone());
+        let sealed = SealedVault::new(b"secret".to_vec(), EncryptionFormat::PLAINTEXT);
+        handler.decrypt(sealed).unwrap();
+        drop(handler);
+
+        let sink = Arc::try_unwrap(sink).ok().unwrap();
+        let log = String::from_utf8(sink.into_inner()).unwrap();
+        assert!(log.ends_with(" Decrypt PLAINTEXT ok 6\n"));
+        assert!(!log.contains("secret"));
+    }
+}
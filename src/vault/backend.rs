@@ -0,0 +1,139 @@
+use super::{EncryptionFormat, SealedVault, UnsealedVault, VaultHandler};
+use failure::{Error, ResultExt};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// Per-call inputs a backend may need besides the bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BackendParams<'a> {
+    /// Fingerprints to encrypt for. Backends without recipients ignore them.
+    pub recipients: &'a [&'a str],
+}
+
+/// The crypto for a single format, without the token and handler plumbing
+/// around it. Register backends in a `BackendRegistry` to use them as a
+/// `VaultHandler`.
+pub trait CryptoBackend {
+    fn format(&self) -> EncryptionFormat;
+    fn seal(&self, plain: &[u8], params: &BackendParams) -> Result<Vec<u8>, Error>;
+    fn open(&self, sealed: &[u8], params: &BackendParams) -> Result<Vec<u8>, Error>;
+}
+
+/// Maps formats to backends. As a handler, it seals with the backend for its
+/// default format, whatever the vault was tagged with, and opens whatever
+/// format it has a backend for.
+pub struct BackendRegistry {
+    default_format: EncryptionFormat,
+    backends: BTreeMap<EncryptionFormat, Box<dyn CryptoBackend>>,
+}
+
+impl BackendRegistry {
+    /// An empty registry sealing with, and advertising, `default_format`.
+    pub fn new(default_format: EncryptionFormat) -> BackendRegistry {
+        BackendRegistry {
+            default_format,
+            backends: BTreeMap::new(),
+        }
+    }
+
+    /// Adds `backend` for its format, returning the one it replaces.
+    pub fn register(&mut self, backend: Box<dyn CryptoBackend>) -> Option<Box<dyn CryptoBackend>> {
+        self.backends.insert(backend.format(), backend)
+    }
+
+    pub fn get(&self, format: &EncryptionFormat) -> Option<&dyn CryptoBackend> {
+        self.backends.get(format).map(|backend| backend.as_ref())
+    }
+
+    fn backend(&self, format: &EncryptionFormat) -> Result<&dyn CryptoBackend, Error> {
+        self.get(format)
+            .ok_or_else(|| format_err!("No backend registered for {} vaults.", format))
+    }
+
+    fn seal_with(&self, u: UnsealedVault, params: &BackendParams) -> Result<SealedVault, Error> {
+        let secret = self
+            .backend(&self.default_format)?
+            .seal(u.plain_secret.as_bytes(), params)?;
+        Ok(SealedVault::new(secret, self.format()))
+    }
+}
+
+impl VaultHandler for BackendRegistry {
+    fn format(&self) -> EncryptionFormat {
+        self.default_format.clone()
+    }
+
+    fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, Error> {
+        self.seal_with(u, &BackendParams::default())
+    }
+
+    fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, Error> {
+        let plain = Zeroizing::new(
+            self.backend(&s.format)?
+                .open(&s.secret, &BackendParams::default())?,
+        );
+        let plain = std::str::from_utf8(&plain).context("Decrypted vault is not valid UTF-8")?;
+        Ok(UnsealedVault::new(plain.to_owned(), s.format))
+    }
+
+    fn encrypt_for(&self, u: UnsealedVault, recipients: &[&str]) -> Result<SealedVault, Error> {
+        self.seal_with(u, &BackendParams { recipients })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ReversingBackend;
+
+    impl CryptoBackend for ReversingBackend {
+        fn format(&self) -> EncryptionFormat {
+            EncryptionFormat::PLAINTEXT
+        }
+
+        fn seal(&self, plain: &[u8], _params: &BackendParams) -> Result<Vec<u8>, Error> {
+            Ok(plain.iter().rev().cloned().collect())
+        }
+
+        fn open(&self, sealed: &[u8], _params: &BackendParams) -> Result<Vec<u8>, Error> {
+            Ok(sealed.iter().rev().cloned().collect())
+        }
+    }
+
+    #[test]
+    fn seals_through_registered_backends() {
+        let mut registry = BackendRegistry::new(EncryptionFormat::PLAINTEXT);
+        assert!(registry.register(Box::new(ReversingBackend)).is_none());
+
+        let sealed = registry
+            .encrypt(UnsealedVault::new(
+                "secret".to_owned(),
+                EncryptionFormat::PLAINTEXT,
+            ))
+            .unwrap();
+        assert_eq!(sealed.secret, b"terces");
+        assert_eq!(*registry.decrypt(sealed).unwrap().plain_secret, "secret");
+
+        let error = registry
+            .decrypt(SealedVault::new(b"abc".to_vec(), EncryptionFormat::GPG_KEY))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "No backend registered for GPG_KEY vaults."
+        );
+    }
+
+    #[test]
+    fn seals_with_the_default_format() {
+        let mut registry = BackendRegistry::new(EncryptionFormat::PLAINTEXT);
+        registry.register(Box::new(ReversingBackend));
+
+        let sealed = registry
+            .encrypt(UnsealedVault::new_default("secret".to_owned()))
+            .unwrap();
+        assert_eq!(sealed.format, registry.format());
+        assert_eq!(sealed.format, EncryptionFormat::PLAINTEXT);
+    }
+}
@@ -0,0 +1,126 @@
+use super::{EncryptionFormat, SealedVault, UnsealedVault, VaultHandler};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use failure::{Error, ResultExt};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const NONCE_LEN: usize = 12;
+const KEY_INFO: &[u8] = b"culper keyfile v1";
+
+/// Seals with AES-256-GCM under a key derived (HKDF-SHA256) from a keyfile,
+/// for machines where distributing GPG keys is impractical. Tokens carry the
+/// random nonce in front of the ciphertext.
+pub struct KeyfileVaultHandler {
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl KeyfileVaultHandler {
+    pub fn from_key_bytes(material: &[u8]) -> Result<KeyfileVaultHandler, Error> {
+        if material.is_empty() {
+            return Err(format_err!("Key material is empty."));
+        }
+        let mut key = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(None, material)
+            .expand(KEY_INFO, &mut key[..])
+            .map_err(|_| format_err!("Could not derive a key from the key material."))?;
+        Ok(KeyfileVaultHandler { key })
+    }
+
+    pub fn from_keyfile<P: AsRef<Path>>(path: P) -> Result<KeyfileVaultHandler, Error> {
+        let path = path.as_ref();
+        let material = Zeroizing::new(
+            fs::read(path).context(format!("Could not read keyfile {}", path.display()))?,
+        );
+        KeyfileVaultHandler::from_key_bytes(&material)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key[..]))
+    }
+}
+
+impl VaultHandler for KeyfileVaultHandler {
+    fn format(&self) -> EncryptionFormat {
+        EncryptionFormat::AES_GCM
+    }
+
+    fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, u.plain_secret.as_bytes())
+            .map_err(|_| format_err!("Encryption with the keyfile key failed."))?;
+        let mut secret = nonce.to_vec();
+        secret.extend_from_slice(&ciphertext);
+        Ok(SealedVault::new(secret, self.format()))
+    }
+
+    fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, Error> {
+        if s.format != EncryptionFormat::AES_GCM {
+            return Err(format_err!(
+                "Keyfile handler cannot decrypt {} vaults.",
+                s.format
+            ));
+        }
+        if s.secret.len() < NONCE_LEN {
+            return Err(format_err!("AES_GCM vault is too short to hold a nonce."));
+        }
+        let (nonce, ciphertext) = s.secret.split_at(NONCE_LEN);
+        let plain = Zeroizing::new(
+            self.cipher()
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| format_err!("Decryption with the keyfile key failed."))?,
+        );
+        let plain = std::str::from_utf8(&plain).context("Decrypted vault is not valid UTF-8")?;
+        Ok(UnsealedVault::new(
+            plain.to_owned(),
+            EncryptionFormat::AES_GCM,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_a_keyfile() {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!("culper-lib-keyfile-{}", ::std::process::id()));
+        fs::write(&path, b"correct horse battery staple").unwrap();
+        let handler = KeyfileVaultHandler::from_keyfile(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let unsealed = UnsealedVault::new("launch codes".to_owned(), EncryptionFormat::AES_GCM);
+        let sealed = handler.encrypt(unsealed).unwrap();
+        assert_eq!(sealed.format, EncryptionFormat::AES_GCM);
+        assert!(!sealed
+            .secret
+            .windows(b"launch".len())
+            .any(|window| window == b"launch"));
+
+        let unsealed = handler.decrypt(sealed).unwrap();
+        assert_eq!(*unsealed.plain_secret, "launch codes");
+    }
+
+    #[test]
+    fn rejects_a_different_key() {
+        let sealing = KeyfileVaultHandler::from_key_bytes(b"ci key").unwrap();
+        let other = KeyfileVaultHandler::from_key_bytes(b"someone else's key").unwrap();
+        let sealed = sealing
+            .encrypt(UnsealedVault::new(
+                "s3cr3t".to_owned(),
+                EncryptionFormat::AES_GCM,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            other.decrypt(sealed).err().unwrap().to_string(),
+            "Decryption with the keyfile key failed."
+        );
+    }
+}
@@ -1,28 +1,138 @@
-use base64::{decode, encode};
+use base64::{decode_config_buf, encode, STANDARD};
 use failure::*;
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::OnceLock;
+use zeroize::Zeroizing;
 
-#[derive(Debug)]
+mod armor;
+mod audit;
+mod backend;
+#[cfg(feature = "keyfile")]
+mod keyfile;
+#[cfg(feature = "password")]
+mod password;
+mod scan;
+
+pub use self::audit::{
+    AuditEntry, AuditOperation, AuditSink, AuditingVaultHandler, WriteAuditSink,
+};
+pub use self::backend::{BackendParams, BackendRegistry, CryptoBackend};
+#[cfg(feature = "keyfile")]
+pub use self::keyfile::KeyfileVaultHandler;
+#[cfg(feature = "password")]
+pub use self::password::PasswordVaultHandler;
+pub use self::scan::{count_tokens, find_tokens, find_tokens_reader, strip_tokens};
+
+pub const DEFAULT_PREFIX: &str = "CULPER";
+
+const VERSION_1: &str = "v1";
+const BASE32: base32::Alphabet = base32::Alphabet::RFC4648 { padding: false };
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[allow(non_camel_case_types)]
 pub enum EncryptionFormat {
+    /// The default. A config setting may pick a different one in the future,
+    /// so rely on `EncryptionFormat::default()` rather than naming it.
+    #[default]
     GPG_KEY,
+    PLAINTEXT,
+    AES_GCM,
+    AES_GCM_PASSWORD,
 }
 
 impl EncryptionFormat {
+    pub fn all() -> &'static [EncryptionFormat] {
+        &[
+            EncryptionFormat::GPG_KEY,
+            EncryptionFormat::PLAINTEXT,
+            EncryptionFormat::AES_GCM,
+            EncryptionFormat::AES_GCM_PASSWORD,
+        ]
+    }
+
     pub fn as_str(&self) -> String {
+        self.as_static_str().to_owned()
+    }
+
+    pub fn as_static_str(&self) -> &'static str {
+        match *self {
+            EncryptionFormat::GPG_KEY => "GPG_KEY",
+            EncryptionFormat::PLAINTEXT => "PLAINTEXT",
+            EncryptionFormat::AES_GCM => "AES_GCM",
+            EncryptionFormat::AES_GCM_PASSWORD => "AES_GCM_PASSWORD",
+        }
+    }
+    /// Whether sealing encrypts to a set of recipients, like GPG, rather than
+    /// with a shared key.
+    pub fn requires_recipients(&self) -> bool {
         match *self {
-            EncryptionFormat::GPG_KEY => String::from("GPG_KEY"),
+            EncryptionFormat::GPG_KEY => true,
+            EncryptionFormat::PLAINTEXT
+            | EncryptionFormat::AES_GCM
+            | EncryptionFormat::AES_GCM_PASSWORD => false,
         }
     }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(value: &str) -> Result<EncryptionFormat, failure::Error> {
         match value {
             "GPG_KEY" => Ok(EncryptionFormat::GPG_KEY),
-            _ => Err(format_err!("Unknown encryption format given: {}", value).into()),
+            "PLAINTEXT" => Ok(EncryptionFormat::PLAINTEXT),
+            "AES_GCM" => Ok(EncryptionFormat::AES_GCM),
+            "AES_GCM_PASSWORD" => Ok(EncryptionFormat::AES_GCM_PASSWORD),
+            _ => match EncryptionFormat::closest_to(value) {
+                Some(suggestion) => Err(format_err!(
+                    "Unknown encryption format given: {}, did you mean {}?",
+                    value,
+                    suggestion
+                )),
+                None => Err(format_err!("Unknown encryption format given: {}", value)),
+            },
         }
     }
+
+    fn closest_to(value: &str) -> Option<&'static EncryptionFormat> {
+        let value = value.to_uppercase();
+        EncryptionFormat::all()
+            .iter()
+            .map(|format| (edit_distance(&value, format.as_static_str()), format))
+            .filter(|(distance, _)| *distance <= 3)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, format)| format)
+    }
+}
+
+impl fmt::Display for EncryptionFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_static_str())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
 }
 
+/// Cloning duplicates the plaintext in memory; every copy is zeroized on drop.
+#[derive(Clone)]
 pub struct UnsealedVault {
-    pub plain_secret: String,
+    pub plain_secret: Zeroizing<String>,
     pub format: EncryptionFormat,
 }
 
@@ -35,10 +145,26 @@ pub trait SealableVault {
 impl UnsealedVault {
     pub fn new(plain_secret: String, format: EncryptionFormat) -> UnsealedVault {
         UnsealedVault {
-            plain_secret,
+            plain_secret: Zeroizing::new(plain_secret),
             format,
         }
     }
+
+    /// A vault in the default format, see `EncryptionFormat::default`.
+    pub fn new_default(plain_secret: String) -> UnsealedVault {
+        UnsealedVault::new(plain_secret, EncryptionFormat::default())
+    }
+
+    pub fn format(&self) -> &EncryptionFormat {
+        &self.format
+    }
+
+    /// Transforms the plaintext, keeping the format. `f` only borrows the
+    /// secret, which is zeroized when the result replaces it.
+    pub fn map_secret(mut self, f: impl FnOnce(&str) -> String) -> UnsealedVault {
+        self.plain_secret = Zeroizing::new(f(&self.plain_secret));
+        self
+    }
 }
 
 impl SealableVault for UnsealedVault {
@@ -46,25 +172,198 @@ impl SealableVault for UnsealedVault {
     where
         F: Fn(UnsealedVault) -> Result<SealedVault, failure::Error>,
     {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "culper_seal",
+            format = %self.format,
+            size = self.plain_secret.len()
+        )
+        .entered();
         f(self)
     }
 }
 
+#[derive(Clone)]
 pub struct SealedVault {
-    pub secret: Vec<u8>,
-    pub format: EncryptionFormat,
+    secret: Vec<u8>,
+    format: EncryptionFormat,
+    // The serialized token, built on first use and cleared by the setters.
+    token: OnceLock<String>,
+}
+
+impl PartialEq for SealedVault {
+    fn eq(&self, other: &SealedVault) -> bool {
+        self.secret == other.secret && self.format == other.format
+    }
+}
+
+impl fmt::Display for SealedVault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.as_token())
+    }
+}
+
+impl fmt::Debug for SealedVault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SealedVault")
+            .field("secret", &self.secret)
+            .field("format", &self.format)
+            .finish()
+    }
 }
 
 pub trait OpenableVault {
     fn unseal<F>(self, f: &F) -> Result<UnsealedVault, failure::Error>
     where
         F: Fn(SealedVault) -> Result<UnsealedVault, failure::Error>;
-    fn to_string(&self) -> String;
 }
 
 impl SealedVault {
     pub fn new(secret: Vec<u8>, format: EncryptionFormat) -> SealedVault {
-        SealedVault { secret, format }
+        SealedVault {
+            secret,
+            format,
+            token: OnceLock::new(),
+        }
+    }
+
+    /// Same as `new`, with the arguments in the order `into_parts` returns them.
+    pub fn from_parts(format: EncryptionFormat, secret: Vec<u8>) -> SealedVault {
+        SealedVault::new(secret, format)
+    }
+
+    /// The format and ciphertext, e.g. to store them outside of a token.
+    pub fn into_parts(self) -> (EncryptionFormat, Vec<u8>) {
+        (self.format, self.secret)
+    }
+
+    /// The `CULPER.<format>.<payload>` token, encoded once and reused until
+    /// `set_secret` or `set_format` change the vault.
+    pub fn as_token(&self) -> Cow<'_, str> {
+        Cow::Borrowed(
+            self.token
+                .get_or_init(|| self.to_string_with_prefix(DEFAULT_PREFIX)),
+        )
+    }
+
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    pub fn set_secret(&mut self, secret: Vec<u8>) {
+        self.secret = secret;
+        self.token = OnceLock::new();
+    }
+
+    pub fn format(&self) -> &EncryptionFormat {
+        &self.format
+    }
+
+    pub fn set_format(&mut self, format: EncryptionFormat) {
+        self.format = format;
+        self.token = OnceLock::new();
+    }
+
+    /// Relabels the ciphertext without touching it, e.g. after re-encrypting
+    /// the bytes out of band.
+    pub fn with_format(self, format: EncryptionFormat) -> SealedVault {
+        SealedVault::new(self.secret, format)
+    }
+
+    /// `CULPER.v1.<format>.b32.<payload>`, which survives systems that change
+    /// the case of everything. `parse` reads both this and the base64 form.
+    pub fn to_string_base32(&self) -> String {
+        format!(
+            "{}.{}.{}.b32.{}",
+            DEFAULT_PREFIX,
+            VERSION_1,
+            self.format.as_static_str(),
+            base32::encode(BASE32, &self.secret)
+        )
+    }
+
+    /// Compact binary form: a length-prefixed format tag followed by the raw
+    /// ciphertext.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tag = self.format.as_static_str();
+        let mut bytes = Vec::with_capacity(1 + tag.len() + self.secret.len());
+        bytes.push(tag.len() as u8);
+        bytes.extend_from_slice(tag.as_bytes());
+        bytes.extend_from_slice(&self.secret);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SealedVault, failure::Error> {
+        let (tag_len, rest) = bytes
+            .split_first()
+            .ok_or_else(|| format_err!("Vault bytes are empty."))?;
+        let tag_len = *tag_len as usize;
+        if rest.len() < tag_len {
+            return Err(format_err!(
+                "Vault bytes are truncated: format tag needs {} bytes, found {}.",
+                tag_len,
+                rest.len()
+            ));
+        }
+        let (tag, secret) = rest.split_at(tag_len);
+        let tag = ::std::str::from_utf8(tag).context("Vault format tag is not valid UTF-8")?;
+        Ok(SealedVault::new(
+            secret.to_vec(),
+            EncryptionFormat::from_str(tag)?,
+        ))
+    }
+
+    pub fn to_string_with_prefix(&self, prefix: &str) -> String {
+        format!(
+            "{}.{}.{}",
+            prefix,
+            self.format.as_static_str(),
+            encode(&self.secret)
+        )
+    }
+
+    /// Re-encrypts the vault from `old` to `new` where it lives. On error the
+    /// vault is left as it was.
+    pub fn reseal_in_place(
+        &mut self,
+        old: &dyn VaultHandler,
+        new: &dyn VaultHandler,
+    ) -> Result<(), failure::Error> {
+        *self = new.encrypt(old.decrypt(self.clone())?)?;
+        Ok(())
+    }
+
+    /// Whether both vaults hold the same secret, even if their ciphertexts
+    /// differ. Needs the keys to decrypt both; the plaintexts are compared in
+    /// constant time and zeroized when done.
+    pub fn same_plaintext(
+        &self,
+        other: &SealedVault,
+        handler: &dyn VaultHandler,
+    ) -> Result<bool, failure::Error> {
+        let ours = handler.decrypt(self.clone())?;
+        let theirs = handler.decrypt(other.clone())?;
+        Ok(constant_time_eq(
+            ours.plain_secret.as_bytes(),
+            theirs.plain_secret.as_bytes(),
+        ))
+    }
+
+    /// Decrypts with the first of `handlers` that succeeds, or reports why each
+    /// of them failed.
+    pub fn try_unseal_any(
+        &self,
+        handlers: &[&dyn VaultHandler],
+    ) -> Result<UnsealedVault, failure::Error> {
+        decrypt_with_any(handlers.iter().copied(), self)
+    }
+
+    /// Checks that `handler` can decrypt the vault. The plaintext is zeroized
+    /// right away and never handed back.
+    pub fn verify(&self, handler: &dyn VaultHandler) -> Result<(), failure::Error> {
+        let unsealed = handler.decrypt(self.clone())?;
+        drop(unsealed);
+        Ok(())
     }
 }
 
@@ -73,33 +372,436 @@ impl OpenableVault for SealedVault {
     where
         F: Fn(SealedVault) -> Result<UnsealedVault, failure::Error>,
     {
-        f(self)
-    }
-
-    fn to_string(&self) -> String {
-        format!("CULPER.{}.{}", self.format.as_str(), encode(&self.secret),)
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "culper_unseal",
+            format = %self.format,
+            size = self.secret.len()
+        )
+        .entered();
+        let result = f(self);
+        #[cfg(feature = "tracing")]
+        {
+            if let Err(error) = &result {
+                // Only the error's type is recorded, its message may quote the payload.
+                tracing::warn!(
+                    category = error.find_root_cause().name().unwrap_or("unknown"),
+                    "unsealing failed"
+                );
+            }
+        }
+        result
     }
 }
 
 pub trait VaultHandler {
+    /// The format `encrypt` tags its output with.
+    fn format(&self) -> EncryptionFormat;
     fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, failure::Error>;
     fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, failure::Error>;
+
+    /// Encrypts every item, stopping at the first error. Handlers with costly
+    /// setup, like a GPG context, can override this to set up once.
+    fn encrypt_many(&self, items: Vec<UnsealedVault>) -> Result<Vec<SealedVault>, failure::Error> {
+        items.into_iter().map(|u| self.encrypt(u)).collect()
+    }
+
+    /// Decrypts every item, stopping at the first error. See `encrypt_many`.
+    fn decrypt_many(&self, items: Vec<SealedVault>) -> Result<Vec<UnsealedVault>, failure::Error> {
+        items.into_iter().map(|s| self.decrypt(s)).collect()
+    }
+
+    /// Seals and unseals a random throwaway value, to catch a misconfigured key
+    /// or missing agent before anything real depends on the handler.
+    fn self_test(&self) -> Result<(), failure::Error> {
+        let probe = format!(
+            "culper-self-test-{:016x}",
+            RandomState::new().build_hasher().finish()
+        );
+        let sealed = self
+            .encrypt(UnsealedVault::new(probe.clone(), self.format()))
+            .context("Handler self-test failed to seal")?;
+        let unsealed = self
+            .decrypt(sealed)
+            .context("Handler self-test failed to unseal")?;
+        if constant_time_eq(unsealed.plain_secret.as_bytes(), probe.as_bytes()) {
+            Ok(())
+        } else {
+            Err(format_err!(
+                "Handler self-test failed: unsealing returned a different value."
+            ))
+        }
+    }
+
+    /// Whether `encrypt_for` can reach the key with this fingerprint, e.g. a GPG
+    /// handler checking its keyring. Handlers without recipients accept any.
+    fn can_encrypt_to(&self, _fingerprint: &str) -> bool {
+        true
+    }
+
+    /// Encrypts for exactly `recipients`. Recipient-based handlers like the GPG
+    /// handler override this; the default ignores them, as symmetric handlers do.
+    fn encrypt_for(
+        &self,
+        u: UnsealedVault,
+        _recipients: &[&str],
+    ) -> Result<SealedVault, failure::Error> {
+        self.encrypt(u)
+    }
+
+    /// Re-encrypts `s` for `new_recipients` without changing its format. The
+    /// default decrypts and calls `encrypt_for`; the GPG handler can instead
+    /// re-encrypt the session key and leave the payload alone.
+    fn rewrap(
+        &self,
+        s: SealedVault,
+        new_recipients: &[&str],
+    ) -> Result<SealedVault, failure::Error> {
+        let unsealed = self.decrypt(s)?;
+        self.encrypt_for(unsealed, new_recipients)
+    }
+}
+
+/// Builds a `VaultHandler` from a pair of closures, the same shape `seal` and
+/// `unseal` take. It advertises the default format unless told otherwise.
+pub struct FnVaultHandler<E, D> {
+    encrypt: E,
+    decrypt: D,
+    format: EncryptionFormat,
+}
+
+impl<E, D> FnVaultHandler<E, D>
+where
+    E: Fn(UnsealedVault) -> Result<SealedVault, failure::Error>,
+    D: Fn(SealedVault) -> Result<UnsealedVault, failure::Error>,
+{
+    pub fn new(encrypt: E, decrypt: D) -> FnVaultHandler<E, D> {
+        FnVaultHandler {
+            encrypt,
+            decrypt,
+            format: EncryptionFormat::default(),
+        }
+    }
+
+    pub fn with_format(mut self, format: EncryptionFormat) -> FnVaultHandler<E, D> {
+        self.format = format;
+        self
+    }
+}
+
+impl<E, D> VaultHandler for FnVaultHandler<E, D>
+where
+    E: Fn(UnsealedVault) -> Result<SealedVault, failure::Error>,
+    D: Fn(SealedVault) -> Result<UnsealedVault, failure::Error>,
+{
+    fn format(&self) -> EncryptionFormat {
+        self.format.clone()
+    }
+
+    fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, failure::Error> {
+        (self.encrypt)(u)
+    }
+
+    fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, failure::Error> {
+        (self.decrypt)(s)
+    }
+}
+
+/// Decrypts with the first handler that succeeds and encrypts with the first
+/// handler, e.g. to read vaults sealed with either key during a rotation.
+pub struct ChainVaultHandler {
+    handlers: Vec<Box<dyn VaultHandler>>,
+}
+
+impl ChainVaultHandler {
+    pub fn new(handlers: Vec<Box<dyn VaultHandler>>) -> ChainVaultHandler {
+        ChainVaultHandler { handlers }
+    }
+
+    fn first(&self) -> Result<&dyn VaultHandler, failure::Error> {
+        match self.handlers.first() {
+            Some(handler) => Ok(handler.as_ref()),
+            None => Err(format_err!("Handler chain is empty.")),
+        }
+    }
+}
+
+impl VaultHandler for ChainVaultHandler {
+    fn format(&self) -> EncryptionFormat {
+        match self.handlers.first() {
+            Some(handler) => handler.format(),
+            None => EncryptionFormat::default(),
+        }
+    }
+
+    fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, failure::Error> {
+        self.first()?.encrypt(u)
+    }
+
+    fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, failure::Error> {
+        decrypt_with_any(self.handlers.iter().map(|handler| handler.as_ref()), &s)
+    }
+
+    fn encrypt_for(
+        &self,
+        u: UnsealedVault,
+        recipients: &[&str],
+    ) -> Result<SealedVault, failure::Error> {
+        self.first()?.encrypt_for(u, recipients)
+    }
+
+    fn encrypt_many(&self, items: Vec<UnsealedVault>) -> Result<Vec<SealedVault>, failure::Error> {
+        self.first()?.encrypt_many(items)
+    }
+
+    fn can_encrypt_to(&self, fingerprint: &str) -> bool {
+        match self.handlers.first() {
+            Some(handler) => handler.can_encrypt_to(fingerprint),
+            None => false,
+        }
+    }
+}
+
+fn decrypt_with_any<'a, I>(handlers: I, s: &SealedVault) -> Result<UnsealedVault, failure::Error>
+where
+    I: IntoIterator<Item = &'a dyn VaultHandler>,
+{
+    let mut failures = Vec::new();
+    for (index, handler) in handlers.into_iter().enumerate() {
+        match handler.decrypt(s.clone()) {
+            Ok(unsealed) => return Ok(unsealed),
+            Err(err) => failures.push(format!("handler {}: {}", index + 1, err)),
+        }
+    }
+
+    if failures.is_empty() {
+        Err(format_err!("No handlers to decrypt with."))
+    } else {
+        Err(format_err!(
+            "All handlers failed to decrypt: {}",
+            failures.join("; ")
+        ))
+    }
+}
+
+/// Stores the secret unencrypted. Only meant for tests and local development.
+pub struct PlaintextVaultHandler;
+
+impl VaultHandler for PlaintextVaultHandler {
+    fn format(&self) -> EncryptionFormat {
+        EncryptionFormat::PLAINTEXT
+    }
+
+    fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, failure::Error> {
+        Ok(SealedVault::new(
+            u.plain_secret.as_bytes().to_vec(),
+            self.format(),
+        ))
+    }
+
+    fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, failure::Error> {
+        match s.format {
+            EncryptionFormat::PLAINTEXT => Ok(UnsealedVault::new(
+                String::from_utf8(s.secret).context("Plaintext vault is not valid UTF-8")?,
+                EncryptionFormat::PLAINTEXT,
+            )),
+            ref format => Err(format_err!(
+                "Plaintext handler cannot decrypt {} vaults.",
+                format
+            )),
+        }
+    }
 }
 
 pub fn parse(value: &str) -> Result<SealedVault, failure::Error> {
+    parse_with_prefix(value, DEFAULT_PREFIX)
+}
+
+/// Like `parse`, but only accepts tokens starting with `prefix`.
+pub fn parse_with_prefix(value: &str, prefix: &str) -> Result<SealedVault, failure::Error> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("culper_parse", size = value.len()).entered();
+    if value.trim().is_empty() {
+        return Err(format_err!(
+            "Could not parse string into Culper vault: the token is empty."
+        ));
+    }
     let value_list: Vec<&str> = value.split('.').collect();
     match value_list.as_slice() {
-        ["CULPER", encryption_format, secret_bytes] => Ok(SealedVault::new(
-            decode(secret_bytes).context("Failed to decode base64 payload")?,
-            EncryptionFormat::from_str(&encryption_format.to_string())?,
+        [found, version, encryption_format, encoding, payload]
+            if *found == prefix && version.eq_ignore_ascii_case(VERSION_1) =>
+        {
+            Ok(SealedVault::new(
+                decode_payload(encoding, payload)?,
+                EncryptionFormat::from_str(encryption_format)?,
+            ))
+        }
+        [found, encryption_format, secret_bytes] if *found == prefix => Ok(SealedVault::new(
+            decode_base64(secret_bytes)?,
+            EncryptionFormat::from_str(encryption_format)?,
+        )),
+        [found, _, _] | [found, _, _, _, _] if *found != prefix => Err(format_err!(
+            "Could not parse string into Culper vault: expected {} prefix, found {}.",
+            prefix,
+            found
+        )),
+        [_, version, ..] if version.eq_ignore_ascii_case(VERSION_1) => Err(format_err!(
+            "Could not parse string into Culper vault: expected 5 segments for a {} token, found {}.",
+            VERSION_1,
+            value_list.len()
+        )),
+        parts if parts.len() > 3 => Err(format_err!(
+            "Could not parse string into Culper vault: expected 3 segments, found {}.",
+            parts.len()
         )),
         _ => Err(format_err!("Could not parse string into Culper vault.")),
     }
 }
 
+/// Checks that `value` is a well-formed token without decrypting it, e.g. in
+/// hooks that run without any keys.
+pub fn validate_token(value: &str) -> Result<(), failure::Error> {
+    parse(value).map(drop)
+}
+
+/// Parses one token per line, skipping blank lines and `#` comments.
+pub fn parse_all(text: &str) -> Vec<Result<SealedVault, failure::Error>> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse)
+        .collect()
+}
+
+// `base64::decode` reserves less than the decoder then resizes to, so it
+// allocates twice. Reserving the decoder's own estimate (6 bytes per 8 input
+// bytes) up front allocates once.
+fn decode_base64(payload: &str) -> Result<Vec<u8>, failure::Error> {
+    let mut secret = Vec::with_capacity(payload.len().div_ceil(8) * 6);
+    decode_config_buf(payload, STANDARD, &mut secret).context("Failed to decode base64 payload")?;
+    Ok(secret)
+}
+
+fn decode_payload(encoding: &str, payload: &str) -> Result<Vec<u8>, failure::Error> {
+    if encoding.eq_ignore_ascii_case("b64") {
+        decode_base64(payload)
+    } else if encoding.eq_ignore_ascii_case("b32") {
+        base32::decode(BASE32, &payload.to_uppercase())
+            .ok_or_else(|| format_err!("Failed to decode base32 payload"))
+    } else {
+        Err(format_err!("Unknown payload encoding given: {}", encoding))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    fn any_format() -> impl Strategy<Value = EncryptionFormat> {
+        prop::sample::select(EncryptionFormat::all().to_vec())
+    }
+
+    fn any_payload() -> impl Strategy<Value = Vec<u8>> {
+        prop::collection::vec(any::<u8>(), 0..512)
+    }
+
+    #[cfg(feature = "tracing")]
+    mod tracing_capture {
+        use std::fmt;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::subscriber::Interest;
+        use tracing::{Event, Metadata, Subscriber};
+
+        #[derive(Clone, Default)]
+        pub struct Capture {
+            pub lines: Arc<Mutex<Vec<String>>>,
+            next_id: Arc<AtomicU64>,
+        }
+
+        struct Line(String);
+
+        impl Visit for Line {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.push_str(&format!(" {}={:?}", field.name(), value));
+            }
+        }
+
+        impl Capture {
+            fn push(&self, name: &str, record: impl FnOnce(&mut Line)) {
+                let mut line = Line(name.to_owned());
+                record(&mut line);
+                self.lines.lock().unwrap().push(line.0);
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+                Interest::sometimes()
+            }
+
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes) -> Id {
+                self.push(span.metadata().name(), |line| span.record(line));
+                Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+            }
+
+            fn record(&self, _: &Id, values: &Record) {
+                self.push("record", |line| values.record(line));
+            }
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event) {
+                self.push("event", |line| event.record(line));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn seal_and_unseal_emit_spans_without_plaintext() {
+        let capture = tracing_capture::Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            let sealed = UnsealedVault::new("topsecret".to_owned(), EncryptionFormat::GPG_KEY)
+                .seal(&|vault: UnsealedVault| {
+                    Ok(SealedVault::new(
+                        rot13(vault.plain_secret.as_bytes()),
+                        vault.format,
+                    ))
+                })
+                .unwrap();
+            let _ = sealed.unseal(&|_| Err(format_err!("bad key for topsecret")));
+        });
+
+        let lines = capture.lines.lock().unwrap();
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("culper_seal") && line.contains("size=9")));
+        assert!(lines.iter().any(|line| line.starts_with("culper_unseal")));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("event") && line.contains("category=")));
+        assert!(lines.iter().all(|line| !line.contains("topsecret")));
+    }
+
+    proptest! {
+        #[test]
+        fn parse_inverts_to_string(payload in any_payload(), format in any_format()) {
+            let vault = SealedVault::new(payload, format);
+            prop_assert_eq!(parse(&vault.to_string()).unwrap(), vault);
+        }
+    }
 
     #[test]
     fn can_encrypt() {
@@ -108,8 +810,8 @@ mod tests {
         let secret_nuclear_codes = nuclear_codes
             .seal(&|vault: UnsealedVault| {
                 let secret = vault.plain_secret.chars().map(|c| match c {
-                    'A'...'M' | 'a'...'m' => ((c as u8) + 13),
-                    'N'...'Z' | 'n'...'z' => ((c as u8) - 13),
+                    'A'..='M' | 'a'..='m' => (c as u8) + 13,
+                    'N'..='Z' | 'n'..='z' => (c as u8) - 13,
                     _ => c as u8,
                 });
 
@@ -121,4 +823,528 @@ mod tests {
             String::from_utf8(secret_nuclear_codes.secret).unwrap()
         );
     }
+
+    #[test]
+    fn parse_reports_too_many_segments() {
+        let error = parse("CULPER.GPG_KEY.YWJj.ZGVm.Z2hp").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Could not parse string into Culper vault: expected 3 segments, found 5."
+        );
+    }
+
+    #[test]
+    fn base64_decoding_allocates_once() {
+        for (token, secret) in [
+            ("CULPER.GPG_KEY.YQ==", &b"a"[..]),
+            ("CULPER.GPG_KEY.YWI=", b"ab"),
+            ("CULPER.GPG_KEY.YWJj", b"abc"),
+            ("CULPER.v1.GPG_KEY.b64.YWJjZA==", b"abcd"),
+            ("CULPER.GPG_KEY.", b""),
+        ]
+        .iter()
+        {
+            let vault = parse(token).unwrap();
+            assert_eq!(vault.secret, *secret);
+            let payload = token.rsplit('.').next().unwrap();
+            assert_eq!(vault.secret.capacity(), payload.len().div_ceil(8) * 6);
+        }
+    }
+
+    #[test]
+    fn validates_token_structure() {
+        assert!(validate_token("CULPER.GPG_KEY.YWJj").is_ok());
+        assert_eq!(
+            validate_token("CULPER.GPG_KEY.not*base64")
+                .err()
+                .unwrap()
+                .to_string(),
+            "Failed to decode base64 payload"
+        );
+        assert_eq!(
+            validate_token("CULPER.RSA.YWJj").err().unwrap().to_string(),
+            "Unknown encryption format given: RSA"
+        );
+    }
+
+    #[test]
+    fn parse_all_keeps_per_line_errors() {
+        let text =
+            "# deploy secrets\nCULPER.GPG_KEY.YWJj\n\nnot-a-token\nCULPER.v1.PLAINTEXT.b32.MFRGG\n";
+        let results = parse_all(text);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().secret, b"abc");
+        assert!(results[1].is_err());
+        assert_eq!(
+            results[2].as_ref().unwrap().format,
+            EncryptionFormat::PLAINTEXT
+        );
+    }
+
+    #[test]
+    fn as_token_reuses_the_encoded_form() {
+        let mut vault = SealedVault::new(b"abc".to_vec(), EncryptionFormat::GPG_KEY);
+        let first = vault.as_token();
+        let second = vault.as_token();
+        assert_eq!(first, "CULPER.GPG_KEY.YWJj");
+        assert_eq!(first, second);
+        match (&first, &second) {
+            (Cow::Borrowed(first), Cow::Borrowed(second)) => {
+                assert_eq!(first.as_ptr(), second.as_ptr())
+            }
+            _ => panic!("expected the cached token to be borrowed"),
+        }
+
+        vault.set_secret(b"def".to_vec());
+        assert_eq!(vault.as_token(), "CULPER.GPG_KEY.ZGVm");
+        vault.set_format(EncryptionFormat::PLAINTEXT);
+        assert_eq!(vault.to_string(), "CULPER.PLAINTEXT.ZGVm");
+        assert_eq!(format!("{}", vault), vault.as_token());
+    }
+
+    #[test]
+    fn with_format_only_relabels() {
+        let vault = SealedVault::new(b"abc".to_vec(), EncryptionFormat::GPG_KEY);
+        let relabeled = vault.clone().with_format(EncryptionFormat::PLAINTEXT);
+
+        assert_eq!(relabeled.secret, vault.secret);
+        assert_eq!(relabeled.format, EncryptionFormat::PLAINTEXT);
+        assert_eq!(vault.to_string(), "CULPER.GPG_KEY.YWJj");
+        assert_eq!(relabeled.to_string(), "CULPER.PLAINTEXT.YWJj");
+    }
+
+    #[test]
+    fn cloned_sealed_vault_serializes_identically() {
+        let vault = SealedVault::new(b"launch".to_vec(), EncryptionFormat::GPG_KEY);
+        let copy = vault.clone();
+        assert_eq!(vault.to_string(), copy.to_string());
+    }
+
+    #[test]
+    fn encrypt_for_defaults_to_ignoring_recipients() {
+        let handler = PlaintextVaultHandler;
+        let sealed = handler
+            .encrypt_for(
+                UnsealedVault::new("secret".to_owned(), EncryptionFormat::PLAINTEXT),
+                &["1234ABCD", "5678EF01"],
+            )
+            .unwrap();
+        let plain = handler
+            .encrypt(UnsealedVault::new(
+                "secret".to_owned(),
+                EncryptionFormat::PLAINTEXT,
+            ))
+            .unwrap();
+
+        assert_eq!(sealed.to_string(), plain.to_string());
+        assert_eq!(*handler.decrypt(sealed).unwrap().plain_secret, "secret");
+    }
+
+    #[test]
+    fn new_default_uses_the_default_format() {
+        assert_eq!(EncryptionFormat::default(), EncryptionFormat::GPG_KEY);
+        let vault = UnsealedVault::new_default("secret".to_owned());
+        assert_eq!(vault.format, EncryptionFormat::default());
+        assert_eq!(*vault.plain_secret, "secret");
+    }
+
+    #[test]
+    fn round_trips_through_parts() {
+        let vault = SealedVault::new(b"secret".to_vec(), EncryptionFormat::AES_GCM);
+        let (format, secret) = vault.clone().into_parts();
+        assert_eq!(format, EncryptionFormat::AES_GCM);
+        assert_eq!(secret, b"secret");
+        assert_eq!(SealedVault::from_parts(format, secret), vault);
+    }
+
+    #[test]
+    fn parse_reports_empty_tokens() {
+        for token in ["", "  \n\t"] {
+            assert_eq!(
+                parse(token).err().unwrap().to_string(),
+                "Could not parse string into Culper vault: the token is empty."
+            );
+        }
+    }
+
+    #[test]
+    fn parse_reports_wrong_prefix() {
+        let error = parse("VAULT.GPG_KEY.YWJj").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Could not parse string into Culper vault: expected CULPER prefix, found VAULT."
+        );
+    }
+
+    #[test]
+    fn fn_vault_handler_round_trips() {
+        let handler = FnVaultHandler::new(
+            |vault: UnsealedVault| {
+                Ok(SealedVault::new(
+                    rot13(vault.plain_secret.as_bytes()),
+                    vault.format,
+                ))
+            },
+            |vault: SealedVault| {
+                Ok(UnsealedVault::new(
+                    String::from_utf8(rot13(&vault.secret))?,
+                    vault.format,
+                ))
+            },
+        );
+
+        let sealed = handler
+            .encrypt(UnsealedVault::new(
+                "zerozerozerozero".to_owned(),
+                EncryptionFormat::GPG_KEY,
+            ))
+            .unwrap();
+        assert_eq!(sealed.secret, b"mrebmrebmrebmreb");
+        assert_eq!(
+            *handler.decrypt(sealed).unwrap().plain_secret,
+            "zerozerozerozero"
+        );
+    }
+
+    fn rot13(value: &[u8]) -> Vec<u8> {
+        value
+            .iter()
+            .map(|&c| match c {
+                b'A'..=b'M' | b'a'..=b'm' => c + 13,
+                b'N'..=b'Z' | b'n'..=b'z' => c - 13,
+                _ => c,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn format_is_readable_through_accessors() {
+        let unsealed = UnsealedVault::new("secret".to_owned(), EncryptionFormat::PLAINTEXT);
+        assert_eq!(unsealed.format(), &EncryptionFormat::PLAINTEXT);
+
+        let sealed = SealedVault::new(b"secret".to_vec(), EncryptionFormat::GPG_KEY);
+        assert_eq!(sealed.format(), &EncryptionFormat::GPG_KEY);
+    }
+
+    #[test]
+    fn unknown_format_suggests_closest_match() {
+        let error = EncryptionFormat::from_str("GPGKEY").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Unknown encryption format given: GPGKEY, did you mean GPG_KEY?"
+        );
+
+        let error = EncryptionFormat::from_str("ROT13").err().unwrap();
+        assert_eq!(error.to_string(), "Unknown encryption format given: ROT13");
+    }
+
+    #[test]
+    fn verify_checks_decryptability() {
+        let handler = PlaintextVaultHandler;
+        let vault = handler
+            .encrypt(UnsealedVault::new(
+                "secret".to_owned(),
+                EncryptionFormat::PLAINTEXT,
+            ))
+            .unwrap();
+        assert!(vault.verify(&handler).is_ok());
+
+        let mut tampered = vault.clone();
+        tampered.secret[0] = 0xff;
+        assert!(tampered.verify(&handler).is_err());
+    }
+
+    #[test]
+    fn custom_prefix_round_trips() {
+        let vault = SealedVault::new(b"secret".to_vec(), EncryptionFormat::GPG_KEY);
+        let token = vault.to_string_with_prefix("ACME");
+
+        assert!(token.starts_with("ACME.GPG_KEY."));
+        assert_eq!(parse_with_prefix(&token, "ACME").unwrap(), vault);
+        assert!(parse(&token).is_err());
+    }
+
+    #[test]
+    fn custom_prefix_rejects_default_prefix() {
+        let vault = SealedVault::new(b"secret".to_vec(), EncryptionFormat::GPG_KEY);
+        let error = parse_with_prefix(&vault.to_string(), "ACME").err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "Could not parse string into Culper vault: expected ACME prefix, found CULPER."
+        );
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let vault = SealedVault::new(vec![0, 1, 2, 255], EncryptionFormat::GPG_KEY);
+        let bytes = vault.to_bytes();
+
+        assert_eq!(&bytes[..8], b"\x07GPG_KEY");
+        assert_eq!(SealedVault::from_bytes(&bytes).unwrap(), vault);
+    }
+
+    #[test]
+    fn from_bytes_rejects_truncated_input() {
+        let bytes = SealedVault::new(vec![1, 2, 3], EncryptionFormat::GPG_KEY).to_bytes();
+
+        assert!(SealedVault::from_bytes(&[]).is_err());
+        assert!(SealedVault::from_bytes(&bytes[..4]).is_err());
+    }
+
+    struct FailingHandler(&'static str);
+
+    impl VaultHandler for FailingHandler {
+        fn format(&self) -> EncryptionFormat {
+            EncryptionFormat::GPG_KEY
+        }
+
+        fn encrypt(&self, _: UnsealedVault) -> Result<SealedVault, failure::Error> {
+            Err(format_err!("{}", self.0))
+        }
+
+        fn decrypt(&self, _: SealedVault) -> Result<UnsealedVault, failure::Error> {
+            Err(format_err!("{}", self.0))
+        }
+
+        fn can_encrypt_to(&self, _: &str) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn chain_falls_through_to_working_handler() {
+        let chain = ChainVaultHandler::new(vec![
+            Box::new(FailingHandler("old key")),
+            Box::new(PlaintextVaultHandler),
+        ]);
+        let sealed = SealedVault::new(b"secret".to_vec(), EncryptionFormat::PLAINTEXT);
+
+        assert_eq!(*chain.decrypt(sealed).unwrap().plain_secret, "secret");
+    }
+
+    #[test]
+    fn chain_asks_its_sealing_handler_for_recipients() {
+        let failing_first = ChainVaultHandler::new(vec![
+            Box::new(FailingHandler("old key")),
+            Box::new(PlaintextVaultHandler),
+        ]);
+        let plaintext_first = ChainVaultHandler::new(vec![
+            Box::new(PlaintextVaultHandler),
+            Box::new(FailingHandler("old key")),
+        ]);
+
+        assert!(!failing_first.can_encrypt_to("12345678"));
+        assert!(plaintext_first.can_encrypt_to("12345678"));
+        assert!(!ChainVaultHandler::new(vec![]).can_encrypt_to("12345678"));
+    }
+
+    #[test]
+    fn chain_reports_every_failed_attempt() {
+        let chain = ChainVaultHandler::new(vec![
+            Box::new(FailingHandler("old key")),
+            Box::new(FailingHandler("new key")),
+        ]);
+        let sealed = SealedVault::new(b"secret".to_vec(), EncryptionFormat::PLAINTEXT);
+
+        assert_eq!(
+            chain.decrypt(sealed).err().unwrap().to_string(),
+            "All handlers failed to decrypt: handler 1: old key; handler 2: new key"
+        );
+    }
+
+    #[test]
+    fn reseals_vault_inside_a_struct() {
+        struct Entry {
+            vault: SealedVault,
+        }
+        let mut entry = Entry {
+            vault: SealedVault::new(b"secret".to_vec(), EncryptionFormat::PLAINTEXT),
+        };
+        let rot13_handler = FnVaultHandler::new(
+            |u: UnsealedVault| {
+                Ok(SealedVault::new(
+                    rot13(u.plain_secret.as_bytes()),
+                    EncryptionFormat::GPG_KEY,
+                ))
+            },
+            |_| Err(format_err!("not needed")),
+        );
+
+        entry
+            .vault
+            .reseal_in_place(&PlaintextVaultHandler, &rot13_handler)
+            .unwrap();
+        assert_eq!(
+            entry.vault,
+            SealedVault::new(b"frperg".to_vec(), EncryptionFormat::GPG_KEY)
+        );
+
+        assert!(entry
+            .vault
+            .reseal_in_place(&FailingHandler("old key"), &PlaintextVaultHandler)
+            .is_err());
+        assert_eq!(entry.vault.secret, b"frperg");
+    }
+
+    #[test]
+    fn compares_plaintext_across_ciphertexts() {
+        // Drops a leading nonce byte, so equal secrets have different ciphertexts.
+        let handler = FnVaultHandler::new(
+            |_| Err(format_err!("not needed")),
+            |s: SealedVault| {
+                Ok(UnsealedVault::new(
+                    String::from_utf8(s.secret[1..].to_vec()).unwrap(),
+                    EncryptionFormat::GPG_KEY,
+                ))
+            },
+        );
+        let first = SealedVault::new(b"\x01secret".to_vec(), EncryptionFormat::GPG_KEY);
+        let second = SealedVault::new(b"\x02secret".to_vec(), EncryptionFormat::GPG_KEY);
+        let other = SealedVault::new(b"\x01secreT".to_vec(), EncryptionFormat::GPG_KEY);
+
+        assert_ne!(first, second);
+        assert!(first.same_plaintext(&second, &handler).unwrap());
+        assert!(!first.same_plaintext(&other, &handler).unwrap());
+    }
+
+    #[test]
+    fn try_unseal_any_uses_first_working_handler() {
+        let sealed = SealedVault::new(b"secret".to_vec(), EncryptionFormat::PLAINTEXT);
+        let unsealed = sealed
+            .try_unseal_any(&[&FailingHandler("old key"), &PlaintextVaultHandler])
+            .unwrap();
+        assert_eq!(*unsealed.plain_secret, "secret");
+
+        assert_eq!(
+            sealed
+                .try_unseal_any(&[&FailingHandler("old key")])
+                .err()
+                .unwrap()
+                .to_string(),
+            "All handlers failed to decrypt: handler 1: old key"
+        );
+    }
+
+    #[test]
+    fn self_test_catches_broken_handlers() {
+        assert!(PlaintextVaultHandler.self_test().is_ok());
+        assert_eq!(
+            FailingHandler("no agent")
+                .self_test()
+                .err()
+                .unwrap()
+                .to_string(),
+            "Handler self-test failed to seal"
+        );
+
+        let lossy = FnVaultHandler::new(
+            |u: UnsealedVault| {
+                Ok(SealedVault::new(
+                    u.plain_secret.as_bytes()[1..].to_vec(),
+                    u.format,
+                ))
+            },
+            |s: SealedVault| {
+                Ok(UnsealedVault::new(
+                    String::from_utf8(s.secret).unwrap(),
+                    s.format,
+                ))
+            },
+        );
+        assert_eq!(
+            lossy.self_test().err().unwrap().to_string(),
+            "Handler self-test failed: unsealing returned a different value."
+        );
+    }
+
+    #[test]
+    fn batch_calls_match_single_calls() {
+        let handler = PlaintextVaultHandler;
+        let secrets = vec!["one", "two", "three"];
+        let unsealed = || {
+            secrets
+                .iter()
+                .map(|secret| UnsealedVault::new(secret.to_string(), EncryptionFormat::PLAINTEXT))
+        };
+
+        let batch = handler.encrypt_many(unsealed().collect()).unwrap();
+        let single: Vec<SealedVault> = unsealed().map(|u| handler.encrypt(u).unwrap()).collect();
+        assert_eq!(batch, single);
+
+        let opened: Vec<String> = handler
+            .decrypt_many(batch)
+            .unwrap()
+            .into_iter()
+            .map(|u| u.plain_secret.to_string())
+            .collect();
+        assert_eq!(opened, secrets);
+    }
+
+    #[test]
+    fn plaintext_handler_advertises_its_format() {
+        let handler = PlaintextVaultHandler;
+        let sealed = handler
+            .encrypt(UnsealedVault::new(
+                "secret".to_owned(),
+                EncryptionFormat::GPG_KEY,
+            ))
+            .unwrap();
+
+        assert_eq!(handler.format(), EncryptionFormat::PLAINTEXT);
+        assert_eq!(sealed.format, handler.format());
+    }
+
+    #[test]
+    fn rewrap_defaults_to_decrypt_and_encrypt_for() {
+        let handler = PlaintextVaultHandler;
+        let sealed = SealedVault::new(b"secret".to_vec(), EncryptionFormat::PLAINTEXT);
+        let rewrapped = handler.rewrap(sealed.clone(), &["1234ABCD"]).unwrap();
+
+        assert_eq!(rewrapped, sealed);
+        assert_eq!(*handler.decrypt(rewrapped).unwrap().plain_secret, "secret");
+    }
+
+    #[test]
+    fn map_secret_transforms_plaintext() {
+        let vault = UnsealedVault::new("  secret\n".to_owned(), EncryptionFormat::PLAINTEXT)
+            .map_secret(|secret| secret.trim().to_owned());
+
+        assert_eq!(*vault.plain_secret, "secret");
+        assert_eq!(vault.format(), &EncryptionFormat::PLAINTEXT);
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        let vault = SealedVault::new(vec![0, 1, 2, 254, 255], EncryptionFormat::GPG_KEY);
+        let token = vault.to_string_base32();
+
+        assert!(token.starts_with("CULPER.v1.GPG_KEY.b32."));
+        assert_eq!(parse(&token).unwrap(), vault);
+    }
+
+    #[test]
+    fn base32_survives_uppercasing() {
+        let vault = SealedVault::new(b"shouting secret".to_vec(), EncryptionFormat::PLAINTEXT);
+        let token = vault.to_string_base32().to_uppercase();
+
+        assert!(token.starts_with("CULPER.V1.PLAINTEXT.B32."));
+        assert_eq!(parse(&token).unwrap(), vault);
+    }
+
+    #[test]
+    fn only_gpg_requires_recipients() {
+        assert!(EncryptionFormat::GPG_KEY.requires_recipients());
+        assert!(!EncryptionFormat::PLAINTEXT.requires_recipients());
+        assert!(!EncryptionFormat::AES_GCM.requires_recipients());
+        assert!(!EncryptionFormat::AES_GCM_PASSWORD.requires_recipients());
+    }
+
+    #[test]
+    fn static_str_matches_owned_str() {
+        for format in EncryptionFormat::all() {
+            assert_eq!(format.as_static_str(), format.as_str());
+            assert_eq!(format.to_string(), format.as_str());
+        }
+    }
 }
@@ -0,0 +1,245 @@
+use super::{EncryptionFormat, SealedVault, UnsealedVault, VaultHandler};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use failure::{Error, ResultExt};
+use zeroize::Zeroizing;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+// Memory (KiB), iterations and lanes, each a big-endian u32.
+const COSTS_LEN: usize = 12;
+const HEADER_LEN: usize = COSTS_LEN + SALT_LEN + NONCE_LEN;
+
+// Pinned rather than taken from `Params::default()`, so a dependency update
+// can't change the costs new vaults are sealed with.
+const M_COST: u32 = 19 * 1024;
+const T_COST: u32 = 2;
+const P_COST: u32 = 1;
+
+// Costs are read from the token, so cap them before running Argon2: a crafted
+// token could otherwise demand gigabytes of memory or minutes of hashing.
+const MAX_M_COST: u32 = 256 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 8;
+
+/// Seals with AES-256-GCM under a key derived (Argon2id) from a passphrase,
+/// for personal use without GPG. Tokens carry the Argon2 costs, a random salt
+/// and the nonce in front of the ciphertext, so every seal derives a fresh key
+/// and opens regardless of the costs the handler seals with.
+pub struct PasswordVaultHandler {
+    passphrase: Zeroizing<String>,
+    params: Params,
+}
+
+impl PasswordVaultHandler {
+    pub fn new(passphrase: Zeroizing<String>) -> Result<PasswordVaultHandler, Error> {
+        let params = Params::new(M_COST, T_COST, P_COST, None)
+            .map_err(|err| format_err!("Invalid Argon2 costs: {}", err))?;
+        PasswordVaultHandler::with_params(passphrase, params)
+    }
+
+    /// Like `new`, with explicit Argon2 costs for sealing.
+    pub fn with_params(
+        passphrase: Zeroizing<String>,
+        params: Params,
+    ) -> Result<PasswordVaultHandler, Error> {
+        if passphrase.is_empty() {
+            return Err(format_err!("Passphrase is empty."));
+        }
+        check_costs(&params)?;
+        Ok(PasswordVaultHandler { passphrase, params })
+    }
+
+    fn cipher(&self, params: Params, salt: &[u8]) -> Result<Aes256Gcm, Error> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key[..])
+            .map_err(|err| format_err!("Could not derive a key from the passphrase: {}", err))?;
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key[..])))
+    }
+
+    fn seal(
+        &self,
+        plain: &[u8],
+        salt: &[u8; SALT_LEN],
+        nonce: &Nonce<<Aes256Gcm as AeadCore>::NonceSize>,
+    ) -> Result<Vec<u8>, Error> {
+        let ciphertext = self
+            .cipher(self.params.clone(), salt)?
+            .encrypt(nonce, plain)
+            .map_err(|_| format_err!("Encryption with the passphrase failed."))?;
+        let mut secret = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        for cost in [
+            self.params.m_cost(),
+            self.params.t_cost(),
+            self.params.p_cost(),
+        ] {
+            secret.extend_from_slice(&cost.to_be_bytes());
+        }
+        secret.extend_from_slice(salt);
+        secret.extend_from_slice(nonce);
+        secret.extend_from_slice(&ciphertext);
+        Ok(secret)
+    }
+}
+
+fn check_costs(params: &Params) -> Result<(), Error> {
+    if params.m_cost() > MAX_M_COST || params.t_cost() > MAX_T_COST || params.p_cost() > MAX_P_COST
+    {
+        return Err(format_err!(
+            "Argon2 costs above {} KiB, {} iterations or {} lanes could not be opened again.",
+            MAX_M_COST,
+            MAX_T_COST,
+            MAX_P_COST
+        ));
+    }
+    Ok(())
+}
+
+impl VaultHandler for PasswordVaultHandler {
+    fn format(&self) -> EncryptionFormat {
+        EncryptionFormat::AES_GCM_PASSWORD
+    }
+
+    fn encrypt(&self, u: UnsealedVault) -> Result<SealedVault, Error> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let secret = self.seal(u.plain_secret.as_bytes(), &salt, &nonce)?;
+        Ok(SealedVault::new(secret, self.format()))
+    }
+
+    fn decrypt(&self, s: SealedVault) -> Result<UnsealedVault, Error> {
+        if s.format != EncryptionFormat::AES_GCM_PASSWORD {
+            return Err(format_err!(
+                "Password handler cannot decrypt {} vaults.",
+                s.format
+            ));
+        }
+        if s.secret.len() < HEADER_LEN {
+            return Err(format_err!(
+                "AES_GCM_PASSWORD vault is too short to hold its costs, salt and nonce."
+            ));
+        }
+        let (costs, rest) = s.secret.split_at(COSTS_LEN);
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let cost = |index: usize| {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&costs[index * 4..index * 4 + 4]);
+            u32::from_be_bytes(bytes)
+        };
+        if cost(0) > MAX_M_COST || cost(1) > MAX_T_COST || cost(2) > MAX_P_COST {
+            return Err(format_err!(
+                "AES_GCM_PASSWORD vault asks for Argon2 costs above the limit."
+            ));
+        }
+        let params = Params::new(cost(0), cost(1), cost(2), None)
+            .map_err(|err| format_err!("AES_GCM_PASSWORD vault has invalid costs: {}", err))?;
+        let plain = Zeroizing::new(
+            self.cipher(params, salt)?
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| format_err!("Decryption with the passphrase failed."))?,
+        );
+        let plain = std::str::from_utf8(&plain).context("Decrypted vault is not valid UTF-8")?;
+        Ok(UnsealedVault::new(
+            plain.to_owned(),
+            EncryptionFormat::AES_GCM_PASSWORD,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap costs so debug test builds stay fast.
+    fn handler(passphrase: &str) -> PasswordVaultHandler {
+        let params = Params::new(1024, 1, 1, None).unwrap();
+        PasswordVaultHandler::with_params(Zeroizing::new(passphrase.to_owned()), params).unwrap()
+    }
+
+    fn seal(handler: &PasswordVaultHandler, secret: &str) -> SealedVault {
+        handler
+            .encrypt(UnsealedVault::new(
+                secret.to_owned(),
+                EncryptionFormat::AES_GCM_PASSWORD,
+            ))
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips_with_the_passphrase() {
+        let handler = handler("correct horse battery staple");
+        let sealed = seal(&handler, "launch codes");
+        assert_eq!(sealed.format, EncryptionFormat::AES_GCM_PASSWORD);
+        assert_ne!(sealed, seal(&handler, "launch codes"));
+
+        let unsealed = handler.decrypt(sealed).unwrap();
+        assert_eq!(*unsealed.plain_secret, "launch codes");
+    }
+
+    #[test]
+    fn rejects_a_wrong_passphrase() {
+        let sealed = seal(&handler("hunter2"), "s3cr3t");
+
+        assert_eq!(
+            handler("hunter3")
+                .decrypt(sealed)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Decryption with the passphrase failed."
+        );
+    }
+
+    // Sealed with the test costs, a salt of 7s and a nonce of 9s.
+    const KNOWN_ANSWER: &str = "CULPER.AES_GCM_PASSWORD.AAAEAAAAAAEAAAABBwcHBwcHBwcHBwcHBwcHBwkJCQkJCQkJCQkJCea3YkPfpULKUpHRRIraXb7d07UNef5wu4lf2dU=";
+
+    #[test]
+    fn opens_a_known_answer_vector() {
+        let sealed = crate::vault::parse(KNOWN_ANSWER).unwrap();
+        let handler = handler("correct horse battery staple");
+        let resealed = handler
+            .seal(
+                b"known answer",
+                &[7u8; SALT_LEN],
+                Nonce::from_slice(&[9u8; NONCE_LEN]),
+            )
+            .unwrap();
+        assert_eq!(resealed, sealed.secret);
+
+        // The costs come from the vault, not from the handler opening it.
+        let default_costs =
+            PasswordVaultHandler::new(Zeroizing::new("correct horse battery staple".to_owned()))
+                .unwrap();
+        let unsealed = default_costs.decrypt(sealed).unwrap();
+        assert_eq!(*unsealed.plain_secret, "known answer");
+    }
+
+    #[test]
+    fn rejects_oversized_costs_before_hashing() {
+        let mut sealed = crate::vault::parse(KNOWN_ANSWER).unwrap();
+        sealed.secret[..4].copy_from_slice(&(4 * 1024 * 1024u32).to_be_bytes());
+
+        assert_eq!(
+            handler("correct horse battery staple")
+                .decrypt(sealed)
+                .err()
+                .unwrap()
+                .to_string(),
+            "AES_GCM_PASSWORD vault asks for Argon2 costs above the limit."
+        );
+        let params = Params::new(MAX_M_COST + 1, 1, 1, None).unwrap();
+        assert!(
+            PasswordVaultHandler::with_params(Zeroizing::new("pw".to_owned()), params).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_an_empty_passphrase() {
+        assert!(PasswordVaultHandler::new(Zeroizing::new(String::new())).is_err());
+    }
+}
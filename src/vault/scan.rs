@@ -0,0 +1,214 @@
+use super::{parse, EncryptionFormat, SealedVault, DEFAULT_PREFIX, VERSION_1};
+use failure::Error;
+use std::io::BufRead;
+
+fn is_segment_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'+' || byte == b'/' || byte == b'='
+}
+
+/// Byte ranges of everything in `text` that looks like a token. Tokens never
+/// span lines, so scanning line by line finds the same ones.
+pub(crate) fn token_spans(text: &str) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut spans = Vec::new();
+    let mut search_from = 0;
+    while let Some(found) = text[search_from..].find(DEFAULT_PREFIX) {
+        let start = search_from + found;
+        let mut end = start + DEFAULT_PREFIX.len();
+        search_from = end;
+        if start > 0 && is_segment_byte(bytes[start - 1]) {
+            continue;
+        }
+
+        let mut segments = 0;
+        while end + 1 < bytes.len() && bytes[end] == b'.' && is_segment_byte(bytes[end + 1]) {
+            end += 1;
+            while end < bytes.len() && is_segment_byte(bytes[end]) {
+                end += 1;
+            }
+            segments += 1;
+        }
+        if segments > 0 {
+            spans.push((start, end));
+            search_from = end;
+        }
+    }
+    spans
+}
+
+pub fn find_tokens(text: &str) -> impl Iterator<Item = Result<SealedVault, Error>> + '_ {
+    token_spans(text)
+        .into_iter()
+        .map(move |(start, end)| parse(&text[start..end]))
+}
+
+/// How many well-formed tokens `text` contains. Tokens are checked in place,
+/// accepting the same ones `parse` does, without decoding their payloads.
+pub fn count_tokens(text: &str) -> usize {
+    token_spans(text)
+        .into_iter()
+        .filter(|&(start, end)| is_well_formed(&text[start..end]))
+        .count()
+}
+
+fn is_well_formed(token: &str) -> bool {
+    let known_format = |name: &str| {
+        EncryptionFormat::all()
+            .iter()
+            .any(|format| format.as_static_str() == name)
+    };
+    let mut segments = token.split('.');
+    let mut next = || segments.next();
+    match (next(), next(), next(), next(), next(), next()) {
+        (Some(prefix), Some(format), Some(payload), None, None, None) => {
+            prefix == DEFAULT_PREFIX && known_format(format) && is_base64(payload)
+        }
+        (Some(prefix), Some(version), Some(format), Some(encoding), Some(payload), None) => {
+            let payload_ok = if encoding.eq_ignore_ascii_case("b64") {
+                is_base64(payload)
+            } else if encoding.eq_ignore_ascii_case("b32") {
+                payload.bytes().all(
+                    |byte| matches!(byte.to_ascii_uppercase(), b'A'..=b'Z' | b'2'..=b'7' | b'='),
+                )
+            } else {
+                false
+            };
+            prefix == DEFAULT_PREFIX
+                && version.eq_ignore_ascii_case(VERSION_1)
+                && known_format(format)
+                && payload_ok
+        }
+        _ => false,
+    }
+}
+
+// What the base64 decoder accepts: at most two `=` at the end, and no length
+// that leaves a single character in the last group.
+fn is_base64(payload: &str) -> bool {
+    let data = payload.trim_end_matches('=');
+    let padding = payload.len() - data.len();
+    let alphabet = data
+        .bytes()
+        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'+' || byte == b'/');
+    let length = matches!(
+        (data.len() % 4, padding),
+        (0, 0) | (2, 0) | (3, 0) | (2, 1) | (2, 2) | (3, 1)
+    );
+    alphabet && length
+}
+
+/// Replaces every token in `text` with what `replacement` returns for it, e.g.
+/// to turn a populated file into a template. Malformed tokens are kept as is.
+pub fn strip_tokens<F>(text: &str, mut replacement: F) -> String
+where
+    F: FnMut(&SealedVault) -> String,
+{
+    let mut stripped = String::with_capacity(text.len());
+    let mut copied_to = 0;
+    for (start, end) in token_spans(text) {
+        if let Ok(vault) = parse(&text[start..end]) {
+            stripped.push_str(&text[copied_to..start]);
+            stripped.push_str(&replacement(&vault));
+            copied_to = end;
+        }
+    }
+    stripped.push_str(&text[copied_to..]);
+    stripped
+}
+
+/// Like `find_tokens`, but reads line by line instead of loading everything.
+pub fn find_tokens_reader<R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<SealedVault, Error>> {
+    reader.lines().flat_map(|line| match line {
+        Ok(line) => find_tokens(&line).collect::<Vec<_>>(),
+        Err(err) => vec![Err(err.into())],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::EncryptionFormat;
+    use proptest::prelude::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn finds_tokens_in_text() {
+        let first = SealedVault::new(b"first".to_vec(), EncryptionFormat::GPG_KEY);
+        let text = format!("password: {}.\nNOTCULPER.GPG_KEY.abc", first);
+        let found: Vec<SealedVault> = find_tokens(&text).map(Result::unwrap).collect();
+
+        assert_eq!(found, vec![first]);
+    }
+
+    #[test]
+    fn finds_tokens_across_reader_lines() {
+        let first = SealedVault::new(b"first".to_vec(), EncryptionFormat::GPG_KEY);
+        let second = SealedVault::new(b"second".to_vec(), EncryptionFormat::PLAINTEXT);
+        let manifest = format!(
+            "db:\n  password: {}\n  user: admin\napi:\n  key: \"{}\" # rotated\n",
+            first, second
+        );
+
+        let found: Vec<SealedVault> = find_tokens_reader(Cursor::new(manifest.clone()))
+            .map(Result::unwrap)
+            .collect();
+        let whole: Vec<SealedVault> = find_tokens(&manifest).map(Result::unwrap).collect();
+
+        assert_eq!(found, vec![first, second]);
+        assert_eq!(found, whole);
+    }
+
+    #[test]
+    fn strips_tokens_into_placeholders() {
+        let first = SealedVault::new(b"first".to_vec(), EncryptionFormat::GPG_KEY);
+        let second = SealedVault::new(b"second".to_vec(), EncryptionFormat::PLAINTEXT);
+        let text = format!(
+            "db = \"{}\"\napi = \"{}\"\nbroken = CULPER.ROT13.YWJj\n",
+            first, second
+        );
+
+        let mut count = 0;
+        let template = strip_tokens(&text, |_| {
+            count += 1;
+            format!("${{secret_{}}}", count)
+        });
+        assert_eq!(
+            template,
+            "db = \"${secret_1}\"\napi = \"${secret_2}\"\nbroken = CULPER.ROT13.YWJj\n"
+        );
+    }
+
+    #[test]
+    fn counts_well_formed_tokens() {
+        let tokens: Vec<String> = ["one", "two", "three"]
+            .iter()
+            .map(|secret| {
+                SealedVault::new(secret.as_bytes().to_vec(), EncryptionFormat::GPG_KEY).to_string()
+            })
+            .collect();
+        let text = format!(
+            "a: {}\nb: {} c: {}\nbroken: CULPER.ROT13.YWJj\n",
+            tokens[0], tokens[1], tokens[2]
+        );
+
+        assert_eq!(count_tokens(&text), 3);
+        assert_eq!(count_tokens("no tokens here"), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn counting_agrees_with_parse(token in "CULPER\\.(v1\\.)?(GPG_KEY|AES_GCM|ROT13)\\.(b64\\.|b32\\.|B32\\.)?[A-Za-z0-9+/=_]{0,9}") {
+            prop_assert_eq!(is_well_formed(&token), parse(&token).is_ok(), "{}", token);
+        }
+    }
+
+    #[test]
+    fn malformed_tokens_are_reported() {
+        let results: Vec<_> = find_tokens("key = CULPER.ROT13.YWJj").collect();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+}